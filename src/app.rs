@@ -1,409 +1,290 @@
-use std::{collections::HashMap, error};
+use std::{
+    env, error,
+    ops::{Range, RangeInclusive},
+    rc::Rc,
+    str::FromStr,
+    sync::mpsc,
+};
 
-use roxmltree::ParsingOptions;
-use rusqlite::Connection;
 use tui::{
-    layout::{Alignment, Rect},
-    style::{Modifier, Style},
+    buffer::Buffer,
+    layout::{Position, Rect},
+    style::{Color, Modifier, Style},
     text::{Line, Span, Text},
-    widgets::ListState,
+    widgets::{ListState, Paragraph, Widget, Wrap},
+};
+
+use crate::config::Config;
+use crate::editor::TextEditor;
+use crate::history::History;
+use crate::keymap::{Action, Keymap};
+use crate::reference::Reference;
+use crate::scriptures::{
+    Book, Chapter, ChapterEntry, ChapterText, FootnoteText, Link, Location, Scriptures, Verse, Work,
 };
+use crate::search::{SearchIndex, SearchResult};
+use crate::state::SessionState;
+use crate::user_data::{self, Annotation, AnnotationKind, Bookmark, Note, UserData};
 
 /// Application result type.
 pub type AppResult<T> = std::result::Result<T, Box<dyn error::Error>>;
 
-pub const NUM_COLUMNS: usize = 3;
-
-#[derive(Debug, Clone)]
-struct SqliteRow {
-    id: String,
-    html_content: String,
-    chapter_title: String,
-    book_title: String,
-}
-
-#[derive(Debug, Default, Clone)]
-struct Scriptures {
-    works: Vec<Work>,
-}
+/// Columns that take keyboard focus: the Work, Book and CH lists, then the
+/// chapter text and footnote panes.
+pub const NUM_COLUMNS: usize = 5;
+pub const TEXT_COLUMN: usize = 3;
+pub const FOOTNOTE_COLUMN: usize = 4;
 
-impl Scriptures {
-    fn new() -> Self {
-        Self::new_failable().unwrap_or_default()
-    }
-
-    fn new_failable() -> AppResult<Self> {
-        const DATABASES: &[(&str, &str)] = &[
-            ("OT", "ot.sqlite"),
-            ("NT", "nt.sqlite"),
-            ("BoM", "bom.sqlite"),
-            ("D&C", "dc.sqlite"),
-            ("PoGP", "pgp.sqlite"),
-        ];
-        let mut works = vec![];
-
-        for (work_title, db) in DATABASES {
-            let conn = Connection::open(db)?;
-            let mut stmt = conn.prepare("SELECT subitem.id, content_html, subitem.title, IIF(nav_collection.nav_section_id IS NULL, nav_item.title, nav_collection.title) FROM subitem_content JOIN subitem ON subitem_content.subitem_id = subitem.id JOIN nav_item ON subitem_content.subitem_id = nav_item.subitem_id JOIN nav_section ON nav_item.nav_section_id = nav_section.id JOIN nav_collection ON nav_collection.id = nav_section.nav_collection_id ORDER BY subitem.position")?;
-            let rows = stmt.query_map([], |row| {
-                Ok(SqliteRow {
-                    id: row.get(0)?,
-                    html_content: row.get(1)?,
-                    chapter_title: row.get(2)?,
-                    book_title: row.get(3)?,
-                })
-            })?;
-
-            let mut books = vec![];
-            let mut book_title = "".to_string();
-            let mut chapters = vec![];
-            for row in rows {
-                let row = &row?;
-
-                if book_title != row.book_title {
-                    if !chapters.is_empty() {
-                        books.push(Book {
-                            title: book_title,
-                            chapters: chapters.clone(),
-                        })
-                    }
+/// Shown at the end of verses that have a note.
+const NOTE_MARKER: &str = " ✎";
 
-                    book_title = row.book_title.clone();
-                    chapters.clear();
-                }
+/// Lines [`add_chapter_header`] puts above a chapter.
+const CHAPTER_HEADER_LINES: usize = 3;
 
-                let mut stmt = conn.prepare("SELECT label_html, content_html, ref_id FROM related_content_item WHERE subitem_id = :id")?;
-                let footnote_rows = stmt.query_map(&[(":id", &row.id)], |row| {
-                    Ok(Footnote {
-                        label_html: row.get(0)?,
-                        content_html: row.get(1)?,
-                        id: row.get(2)?,
-                    })
-                })?;
-
-                let footnotes = footnote_rows.into_iter().flatten();
-                let mut footnote_map = HashMap::new();
-                for footnote in footnotes {
-                    footnote_map.insert(footnote.id.clone(), footnote);
-                }
+/// How many chapters after the selected one are kept rendered in reader mode.
+const READER_CACHE_CAPACITY: usize = 4;
 
-                chapters.push(Chapter {
-                    title: row.chapter_title.clone(),
-                    html_content: row.html_content.clone(),
-                    footnotes: footnote_map,
-                });
-            }
+/// Moves a list selection one step, wrapping around at either end.
+fn step_selection(selected: Option<usize>, len: usize, down: bool) -> Option<usize> {
+    if len == 0 {
+        return None;
+    }
 
-            if !chapters.is_empty() {
-                books.push(Book {
-                    title: book_title,
-                    chapters,
-                })
-            }
+    let i = match selected {
+        Some(i) if down => (i + 1) % len,
+        Some(0) => len - 1,
+        Some(i) => i - 1,
+        None => 0,
+    };
+    Some(i)
+}
 
-            works.push(Work {
-                title: work_title.to_string(),
-                books,
-            })
+/// Adds [`NOTE_MARKER`] to the end of each verse with a note.
+fn add_note_markers(text: &mut Text<'static>, verses: &[Verse], notes: &[Note]) {
+    for verse in verses {
+        if notes.iter().any(|n| n.paragraph_aid == verse.aid)
+            && let Some(line) = text.lines.get_mut(verse.line)
+        {
+            line.spans
+                .push(Span::styled(NOTE_MARKER, Style::default().fg(Color::Cyan)));
         }
+    }
+}
 
-        Ok(Scriptures { works })
+/// Puts the chapter's title above its text, to divide it from the chapter
+/// before it in reader mode.
+fn add_chapter_header(text: &mut Text<'static>, verses: &mut [Verse], title: &str) {
+    let header: [Line<'static>; CHAPTER_HEADER_LINES] = [
+        Line::default(),
+        Line::styled(
+            format!("━━━ {title} ━━━"),
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        )
+        .centered(),
+        Line::default(),
+    ];
+    text.lines.splice(0..0, header.iter().cloned());
+    for verse in verses {
+        verse.line += CHAPTER_HEADER_LINES;
     }
 }
 
-#[derive(Debug, Default, Clone)]
-struct Work {
-    title: String,
-    books: Vec<Book>,
+/// Which chapter was rendered, and for what pane widths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RenderKey {
+    work: Option<usize>,
+    book: Option<usize>,
+    chapter: Option<usize>,
+    text_width: u16,
+    reader_mode: bool,
 }
 
-#[derive(Debug, Default, Clone)]
-struct Book {
-    title: String,
-    chapters: Vec<Chapter>,
+/// The selected chapter rendered to [`Text`], kept until the selection or the
+/// terminal size changes.
+#[derive(Debug)]
+pub struct RenderedChapter {
+    key: RenderKey,
+    pub text: Text<'static>,
+    pub verses: Vec<Verse>,
+    /// Row at which each line of `text` starts once wrapped to the text pane.
+    line_starts: Vec<u16>,
+    /// Number of rows `text` takes once wrapped to the text pane.
+    pub line_count: u16,
+    pub footnotes: Vec<FootnoteText>,
 }
 
-#[derive(Debug, Default, Clone)]
-struct Chapter {
-    title: String,
-    html_content: String,
-    footnotes: HashMap<String, Footnote>,
+impl RenderedChapter {
+    /// Row of the text pane at which `verse` begins.
+    pub fn verse_row(&self, verse: &Verse) -> u16 {
+        self.line_starts
+            .get(verse.line)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Index of the line of `text` shown at `row` of the text pane, and which
+    /// of the line's wrapped rows that is.
+    fn line_at_row(&self, row: u16) -> Option<(usize, u16)> {
+        if row >= self.line_count {
+            return None;
+        }
+        let line = self.line_starts.partition_point(|&start| start <= row);
+        let line = line.checked_sub(1)?;
+        Some((line, row - self.line_starts[line]))
+    }
 }
 
-impl Chapter {
-    fn footnotes_text(&self) -> Text {
-        let refs_in_order = self.refs_in_order();
-        let mut result = Text::default();
-        for ref_id in &refs_in_order {
-            if let Some(footnote) = self.footnotes.get(ref_id) {
-                let wrapped_label = format!("<p>{}</p>", footnote.label_html);
-                let title_tree = roxmltree::Document::parse_with_options(
-                    &wrapped_label,
-                    ParsingOptions {
-                        allow_dtd: true,
-                        ..Default::default()
-                    },
-                )
-                .unwrap();
-                let mut title = String::new();
-                recursive_text_as_string(title_tree.root(), &mut title);
-
-                let content_tree = roxmltree::Document::parse_with_options(
-                    &footnote.content_html,
-                    ParsingOptions {
-                        allow_dtd: true,
-                        ..Default::default()
-                    },
-                )
-                .unwrap();
-                let mut content = String::new();
-                recursive_text_as_string(content_tree.root(), &mut content);
-
-                let line = Line::from(vec![
-                    Span::styled(title, Style::default().add_modifier(Modifier::BOLD)),
-                    Span::raw(content),
-                ]);
-
-                result.extend(Text::from(line));
-            }
-        }
-
-        result
-    }
-
-    fn refs_in_order(&self) -> Vec<String> {
-        let tree = roxmltree::Document::parse_with_options(
-            &self.html_content,
-            ParsingOptions {
-                allow_dtd: true,
-                ..Default::default()
-            },
-        )
-        .unwrap();
-        let nodes = tree
-            .descendants()
-            .filter(|n| n.attribute("class") == Some("study-note-ref"));
-        let data_refs = nodes.filter_map(|n| n.attribute("data-ref"));
-        data_refs.map(|r| r.into()).collect()
+/// Index of the span of `line` drawn at `column` of its `row`th row when
+/// wrapped to `width` columns.
+///
+/// The line is drawn to a scratch buffer with each span's index as its color,
+/// so it wraps exactly as it does on screen.
+fn span_at(line: &Line<'static>, width: u16, row: u16, column: u16) -> Option<usize> {
+    let mut line = line.clone();
+    line.style = Style::default();
+    for (i, span) in line.spans.iter_mut().enumerate() {
+        let [_, r, g, b] = (i as u32).to_be_bytes();
+        span.style = Style::default().fg(Color::Rgb(r, g, b));
+    }
+
+    let area = Rect::new(0, 0, width, row.saturating_add(1));
+    let mut buffer = Buffer::empty(area);
+    Paragraph::new(line)
+        .wrap(Wrap { trim: false })
+        .render(area, &mut buffer);
+    match buffer.cell((column, row))?.fg {
+        Color::Rgb(r, g, b) => Some(u32::from_be_bytes([0, r, g, b]) as usize),
+        _ => None,
     }
 }
 
-#[derive(Debug, Default, Clone)]
-struct Footnote {
-    id: String,
-    label_html: String,
-    content_html: String,
+/// Row at which each line of `text` starts when wrapped to `width` columns.
+fn wrapped_line_starts(text: &Text<'static>, width: u16) -> Vec<u16> {
+    let mut row = 0;
+    let mut starts = Vec::with_capacity(text.lines.len());
+    for line in &text.lines {
+        starts.push(row);
+        row += Paragraph::new(line.clone())
+            .wrap(Wrap { trim: false })
+            .line_count(width) as u16;
+    }
+    starts
 }
 
-impl Chapter {
-    fn text(&self) -> Text {
-        let mut text = Text::default();
+/// Number of rows `text` takes when wrapped to `width` columns.
+fn wrapped_line_count(text: &Text<'static>, width: u16) -> u16 {
+    Paragraph::new(text.clone())
+        .wrap(Wrap { trim: false })
+        .line_count(width) as u16
+}
 
-        let tree = roxmltree::Document::parse_with_options(
-            &self.html_content,
-            ParsingOptions {
-                allow_dtd: true,
-                ..Default::default()
-            },
-        )
-        .unwrap();
-        if let Some(body) = tree.descendants().find(|n| n.tag_name().name() == "body") {
-            let header = body.descendants().find(|n| n.tag_name().name() == "header");
-            if let Some(header) = header {
-                if let Some(title_node) = header
-                    .children()
-                    .find(|n| n.attribute("id") == Some("title1"))
-                {
-                    let mut title_text = String::new();
-                    recursive_text_as_string(title_node, &mut title_text);
-
-                    let line = Line {
-                        spans: vec![Span {
-                            content: title_text.into(),
-                            style: Style::default().add_modifier(Modifier::BOLD),
-                        }],
-                        alignment: Some(Alignment::Center),
-                        ..Default::default()
-                    };
-                    text.extend(Text {
-                        lines: vec![line],
-                        ..Default::default()
-                    });
-                }
+/// A chapter after the selected one, rendered to be drawn below it in reader
+/// mode.
+#[derive(Debug)]
+struct ReaderChapter {
+    location: Location,
+    text_width: u16,
+    text: Text<'static>,
+    /// Number of rows `text` takes once wrapped to the text pane.
+    line_count: u16,
+}
 
-                if let Some(subtitle_node) = header
-                    .children()
-                    .find(|n| n.attribute("id") == Some("subtitle1"))
-                {
-                    let mut subtitle_text = String::new();
-                    recursive_text_as_string(subtitle_node, &mut subtitle_text);
-
-                    let line = Line {
-                        spans: vec![Span {
-                            content: subtitle_text.into(),
-                            style: Style::default().add_modifier(Modifier::BOLD),
-                        }],
-                        alignment: Some(Alignment::Center),
-                        ..Default::default()
-                    };
-                    text.extend(Text {
-                        lines: vec![line],
-                        ..Default::default()
-                    });
-                }
+/// A scrollable pane.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pane {
+    Text,
+    Footnotes,
+}
 
-                if let Some(intro_node) = header
-                    .children()
-                    .find(|n| n.attribute("id") == Some("intro1"))
-                {
-                    let mut intro_text = String::new();
-                    recursive_text_as_string(intro_node, &mut intro_text);
-                    text.extend(Text::raw(""));
-                    text.extend(Text::raw(intro_text));
-                }
+/// How far to scroll a pane.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scroll {
+    Line,
+    HalfPage,
+    /// A pane's height, less a row kept for context.
+    Page,
+    /// All the way to the top or bottom.
+    End,
+}
 
-                if let Some(study_summary_node) = header
-                    .children()
-                    .find(|n| n.attribute("class") == Some("study-summary"))
-                {
-                    let mut summary_text = String::new();
-                    recursive_text_as_string(study_summary_node, &mut summary_text);
-                    text.extend(Text::raw(""));
-                    text.extend(Text::styled(
-                        summary_text,
-                        Style::default().add_modifier(Modifier::ITALIC),
-                    ));
-                    text.extend(Text::raw("")); // Empty line
-                }
-            }
+/// What keyboard input is currently directed at.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum Mode {
+    /// Navigating the lists and reading.
+    #[default]
+    Normal,
+    /// Typing into the prompt in the status line.
+    Prompt(Prompt),
+    /// Choosing from the results of a search.
+    SearchResults,
+    /// Choosing a bookmark to go to.
+    Bookmarks,
+    /// Writing a note in the note panel.
+    EditingNote(TextEditor),
+    /// Reading the list of key bindings.
+    Help,
+}
 
-            let verses = body
-                .descendants()
-                .filter(|n| n.attribute("class") == Some("verse"));
-            for verse in verses {
-                let verse_text = verse_text(verse);
-                text.extend(Text {
-                    lines: vec![verse_text, "".into()],
-                    ..Default::default()
-                });
-            }
-        }
+/// Text being typed into the status line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub input: String,
+}
 
-        text
-    }
+/// What a [`Prompt`] is asking for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptKind {
+    /// A scripture reference to go to.
+    Goto,
+    /// Words to search for.
+    Search,
+    /// Name of the list to add a bookmark to.
+    Bookmark,
+    /// Color, and optionally words, to mark the selected verses with.
+    Annotate(AnnotationKind),
 }
 
-fn recursive_text_as_string(node: roxmltree::Node, s: &mut String) {
-    if node.is_text() {
-        if let Some(t) = node.text() {
-            s.push_str(t);
+impl PromptKind {
+    /// Text shown before the input.
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            PromptKind::Goto => ":",
+            PromptKind::Search => "/",
+            PromptKind::Bookmark => "Bookmark in list: ",
+            PromptKind::Annotate(AnnotationKind::Highlight) => "Highlight (color [words]): ",
+            PromptKind::Annotate(AnnotationKind::Underline) => "Underline (color [words]): ",
         }
     }
-
-    for n in node.children() {
-        recursive_text_as_string(n, s);
-    }
 }
 
-fn verse_text(node: roxmltree::Node) -> Line<'static> {
-    let mut line = Line::default();
-
-    for child in node.children() {
-        if child.attribute("class") == Some("verse-number") {
-            let verse_num_text = Span::styled(
-                child.text().unwrap().to_string(),
-                Style::default().add_modifier(Modifier::BOLD),
-            );
-            line.spans.push(verse_num_text);
-        } else if child.attribute("class") == Some("para-mark") || child.is_text() {
-            line.spans
-                .push(Span::raw(child.text().unwrap().to_string()))
-        } else if child.attribute("class") == Some("clarity-word") {
-            if let Some(text) = child.text() {
-                // Sometimes clarity word just wraps some text.
-                let clarity_text = Span::styled(
-                    text.to_string(),
-                    Style::default().add_modifier(Modifier::ITALIC),
-                );
-                line.spans.push(clarity_text);
-            } else {
-                // Other times it wraps a reference.
-                for child in child.children() {
-                    if child.attribute("class") == Some("study-note-ref") {
-                        for child2 in child.children() {
-                            if child2.tag_name().name() == "sup" {
-                                if let Some(footnote) = footnote_unicode(child2.text()) {
-                                    line.spans.push(Span::styled(
-                                        footnote,
-                                        Style::default().add_modifier(Modifier::ITALIC),
-                                    ));
-                                }
-                            } else if child2.is_text() {
-                                line.spans.push(Span::styled(
-                                    child2.text().unwrap().to_string(),
-                                    Style::default().add_modifier(Modifier::ITALIC),
-                                ))
-                            }
-                        }
-                    } else if child.is_text() {
-                        line.spans.push(Span::styled(
-                            child.text().unwrap().to_string(),
-                            Style::default().add_modifier(Modifier::ITALIC),
-                        ))
-                    }
-                }
-            }
-        } else if child.attribute("class") == Some("study-note-ref") {
-            for child2 in child.children() {
-                if child2.tag_name().name() == "sup" {
-                    if let Some(footnote) = footnote_unicode(child2.text()) {
-                        line.spans.push(Span::styled(
-                            footnote,
-                            Style::default().add_modifier(Modifier::ITALIC),
-                        ));
-                    }
-                } else if child2.is_text() {
-                    line.spans
-                        .push(Span::raw(child2.text().unwrap().to_string()))
-                }
-            }
-        }
-    }
+/// A note to be edited by an external editor, which needs the terminal to
+/// itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalEdit {
+    /// Command to run, such as `vim`.
+    pub editor: String,
+    pub text: String,
+}
 
-    line
+/// A row of the bookmarks panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookmarkRow<'a> {
+    /// Heading naming a list.
+    List(&'a str),
+    /// Index into [`App::bookmarks`].
+    Bookmark(usize),
 }
 
-fn footnote_unicode(string: Option<&str>) -> Option<&'static str> {
-    let input = string?;
-    match input {
-        "a" => Some("ᵃ"),
-        "b" => Some("ᵇ"),
-        "c" => Some("ᶜ"),
-        "d" => Some("ᵈ"),
-        "e" => Some("ᵉ"),
-        "f" => Some("ᶠ"),
-        "g" => Some("ᵍ"),
-        "h" => Some("ʰ"),
-        "i" => Some("ⁱ"),
-        "j" => Some("ʲ"),
-        "k" => Some("ᵏ"),
-        "l" => Some("ˡ"),
-        "m" => Some("ᵐ"),
-        "n" => Some("ⁿ"),
-        "o" => Some("ᵒ"),
-        "p" => Some("ᵖ"),
-        "q" => Some("q"),
-        "r" => Some("ʳ"),
-        "s" => Some("ˢ"),
-        "t" => Some("ᵗ"),
-        "u" => Some("ᵘ"),
-        "v" => Some("ᵛ"),
-        "w" => Some("ʷ"),
-        "x" => Some("ˣ"),
-        "y" => Some("ʸ"),
-        "z" => Some("ᶻ"),
-        _ => None,
-    }
+/// Somewhere the reader has been, to return to from the history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Place {
+    pub location: Location,
+    pub text_scroll: u16,
+    pub verse_cursor: Option<usize>,
+    pub verse_anchor: Option<usize>,
 }
 
 /// Application.
@@ -411,97 +292,484 @@ fn footnote_unicode(string: Option<&str>) -> Option<&'static str> {
 pub struct App {
     /// Is the application running?
     pub running: bool,
+    pub mode: Mode,
+    pub keymap: Keymap,
+    /// Does scrolling past either end of a chapter go on to the next or
+    /// previous one?
+    pub auto_continue: bool,
+    /// Are the lists hidden, and the chapters shown one after another as a
+    /// single document? The selected chapter is always the one at the top
+    /// of the text pane.
+    pub reader_mode: bool,
+    /// Chapters drawn below the selected one in reader mode, least recently
+    /// rendered first.
+    reader_chapters: Vec<ReaderChapter>,
+    /// Rows the key bindings help is scrolled by.
+    pub help_scroll: u16,
     data: Scriptures,
     pub column_selected: usize,
     pub works_state: ListState,
     pub books_state: ListState,
     pub chapters_state: ListState,
+    /// Content of the selected chapter.
+    chapter: Option<Rc<Chapter>>,
+    /// The reader's highlights and underlines in the selected chapter.
+    annotations: Vec<Annotation>,
+    /// The reader's notes on verses in the selected chapter.
+    notes: Vec<Note>,
+    rendered: Option<RenderedChapter>,
 
     pub text_rect: Rect,
     pub text_scroll: u16,
 
     pub footnote_rect: Rect,
     pub footnote_scroll: u16,
+
+    /// Where the rows of the Work, Book and CH lists are drawn.
+    pub list_rects: [Rect; 3],
+    /// Verse a drag with the mouse started on, which the selection extends
+    /// from.
+    drag_start: Option<usize>,
+
+    /// Index into [`RenderedChapter::verses`] of the verse the cursor is on.
+    pub verse_cursor: Option<usize>,
+    /// Other end of the verse selection while extending it.
+    verse_anchor: Option<usize>,
+    /// Index into the cursor verse's [`Verse::markers`] of the footnote being
+    /// read in full.
+    pub selected_footnote: Option<usize>,
+    /// Index into the links of the footnotes shown of the cross-reference to
+    /// follow.
+    pub selected_link: Option<usize>,
+
+    /// Places left for another chapter or verse.
+    history: History<Place>,
+    /// Where moving through the Works, Books and CH lists last left the
+    /// reader. Moving on from there isn't recorded again, so going back skips
+    /// the list items passed on the way.
+    browsed_to: Option<Place>,
+
+    /// Message shown in the status line at the bottom of the screen.
+    pub status: Option<String>,
+
+    /// Opened on the first search.
+    search_index: Option<SearchIndex>,
+    /// Set while the search index is opened in the background, along with
+    /// the query to search for once it is.
+    indexing: Option<(mpsc::Receiver<Result<SearchIndex, String>>, String)>,
+    pub search_results: Vec<SearchResult>,
+    pub search_results_state: ListState,
+
+    /// Opened the first time it's needed.
+    user_data: Option<UserData>,
+    /// Every bookmark, grouped by list, while the bookmarks panel is open.
+    pub bookmarks: Vec<Bookmark>,
+    /// Selected row of [`App::bookmark_rows`].
+    pub bookmarks_state: ListState,
+
+    /// Is the note panel beside the chapter text shown?
+    pub note_panel: bool,
+    /// The note being edited, as it was before editing.
+    editing_note: Option<Note>,
+    /// Set when a note should be edited in `$EDITOR`. The main loop hands it
+    /// the terminal and passes the result to [`App::finish_external_edit`].
+    pub external_edit: Option<ExternalEdit>,
 }
 
-impl Default for App {
-    fn default() -> Self {
-        Self {
+impl App {
+    /// Constructs a new instance of [`App`] reading from `data`.
+    pub fn new(data: Scriptures) -> Self {
+        let errors = data.errors();
+        let status = if data.works.is_empty() {
+            Some(format!(
+                "No scripture databases found in {}",
+                data.data_dir.display()
+            ))
+        } else if errors.is_empty() {
+            None
+        } else {
+            Some(format!("Unable to load {}", errors.join("; ")))
+        };
+
+        // Start on the first work that actually loaded.
+        let first_work = data.works.iter().position(|w| w.error.is_none());
+        let first_book = first_work.map(|_| 0);
+        let selected_work = first_work.or(data.works.first().map(|_| 0));
+
+        let mut app = Self {
             running: true,
-            data: Scriptures::new(),
+            mode: Mode::default(),
+            keymap: Keymap::default(),
+            auto_continue: false,
+            reader_mode: false,
+            reader_chapters: vec![],
+            help_scroll: 0,
+            data,
             column_selected: 0,
-            works_state: ListState::default().with_selected(Some(0)),
-            books_state: ListState::default().with_selected(Some(0)),
-            chapters_state: ListState::default().with_selected(Some(0)),
+            works_state: ListState::default().with_selected(selected_work),
+            books_state: ListState::default().with_selected(first_book),
+            chapters_state: ListState::default().with_selected(first_book),
+            chapter: None,
+            annotations: vec![],
+            notes: vec![],
+            rendered: None,
 
             text_rect: Rect::default(),
             text_scroll: 0,
 
             footnote_rect: Rect::default(),
             footnote_scroll: 0,
-        }
+
+            list_rects: [Rect::default(); 3],
+            drag_start: None,
+
+            verse_cursor: None,
+            verse_anchor: None,
+            selected_footnote: None,
+            selected_link: None,
+
+            history: History::default(),
+            browsed_to: None,
+
+            status,
+
+            search_index: None,
+            indexing: None,
+            search_results: vec![],
+            search_results_state: ListState::default(),
+
+            user_data: None,
+            bookmarks: vec![],
+            bookmarks_state: ListState::default(),
+
+            note_panel: false,
+            editing_note: None,
+            external_edit: None,
+        };
+        app.load_chapter();
+        app
     }
-}
 
-impl App {
-    /// Constructs a new instance of [`App`].
-    pub fn new() -> Self {
-        Self::default()
+    /// Applies the reader's settings, reporting any that can't be.
+    pub fn configure(&mut self, config: &Config) {
+        let (keymap, errors) = Keymap::from_config(config);
+        self.keymap = keymap;
+        self.auto_continue = config.auto_continue;
+        if !errors.is_empty() {
+            self.status = Some(format!("Unable to bind keys: {}", errors.join("; ")));
+        }
     }
 
     /// Handles the tick event of the terminal.
-    pub fn tick(&self) {}
+    pub fn tick(&mut self) {
+        self.finish_indexing();
+    }
 
     /// Set running to false to quit the application.
     pub fn quit(&mut self) {
         self.running = false;
     }
 
-    fn current_chapter(&self) -> &Chapter {
-        &self.data.works[self.works_state.selected().unwrap_or_default()].books
-            [self.books_state.selected().unwrap_or_default()]
-        .chapters[self.chapters_state.selected().unwrap_or_default()]
+    fn current_work(&self) -> Option<&Work> {
+        self.data
+            .works
+            .get(self.works_state.selected().unwrap_or_default())
+    }
+
+    fn current_book(&self) -> Option<&Book> {
+        self.current_work()?
+            .books
+            .get(self.books_state.selected().unwrap_or_default())
+    }
+
+    fn current_chapter_entry(&self) -> Option<&ChapterEntry> {
+        self.current_book()?
+            .chapters
+            .get(self.chapters_state.selected().unwrap_or_default())
+    }
+
+    fn current_chapter(&self) -> Option<&Chapter> {
+        self.chapter.as_deref()
+    }
+
+    /// Resets the panes for a newly selected chapter and fetches its content.
+    fn chapter_changed(&mut self) {
+        self.text_scroll = 0;
+        self.footnote_scroll = 0;
+        self.verse_cursor = None;
+        self.verse_anchor = None;
+        self.selected_footnote = None;
+        self.selected_link = None;
+        self.load_chapter();
+    }
+
+    /// Fetches the content of the selected chapter.
+    fn load_chapter(&mut self) {
+        let chapter = self.data.chapter(
+            self.works_state.selected().unwrap_or_default(),
+            self.books_state.selected().unwrap_or_default(),
+            self.chapters_state.selected().unwrap_or_default(),
+        );
+
+        self.chapter = match chapter {
+            Ok(chapter) => chapter,
+            Err(e) => {
+                self.status = Some(format!("Unable to load chapter: {e}"));
+                None
+            }
+        };
+        self.load_annotations();
+        self.load_notes();
+    }
+
+    /// Fetches the reader's notes in the selected chapter.
+    fn load_notes(&mut self) {
+        let ids = self
+            .current_work()
+            .zip(self.current_chapter_entry())
+            .map(|(w, c)| (w.uri.clone(), c.id.clone()));
+        let Some((work_uri, chapter_id)) = ids else {
+            self.notes = vec![];
+            return;
+        };
+
+        self.notes = match self.user_data().map(|u| u.notes(&work_uri, &chapter_id)) {
+            Some(Ok(notes)) => notes,
+            Some(Err(e)) => {
+                self.status = Some(format!("Unable to load notes: {e}"));
+                vec![]
+            }
+            None => vec![],
+        };
+        // Render again with the note markers.
+        self.forget_rendered();
+    }
+
+    /// Fetches the reader's annotations in the selected chapter.
+    fn load_annotations(&mut self) {
+        let ids = self
+            .current_work()
+            .zip(self.current_chapter_entry())
+            .map(|(w, c)| (w.uri.clone(), c.id.clone()));
+        let Some((work_uri, chapter_id)) = ids else {
+            self.annotations = vec![];
+            return;
+        };
+
+        self.annotations = match self
+            .user_data()
+            .map(|u| u.annotations(&work_uri, &chapter_id))
+        {
+            Some(Ok(annotations)) => annotations,
+            Some(Err(e)) => {
+                self.status = Some(format!("Unable to load annotations: {e}"));
+                vec![]
+            }
+            None => vec![],
+        };
+        // Render again with the annotations.
+        self.forget_rendered();
+    }
+
+    /// Drops the renderings of the selected chapter, once what's drawn on it
+    /// has changed.
+    fn forget_rendered(&mut self) {
+        self.rendered = None;
+        let location = self.current_location();
+        self.reader_chapters
+            .retain(|c| Some(c.location) != location);
     }
 
     pub fn chapter_title(&self) -> String {
-        let chapter = self.current_chapter();
-        chapter.title.clone()
+        match (self.current_work(), self.current_chapter_entry()) {
+            (_, Some(entry)) => entry.title.clone(),
+            (Some(work), None) if work.error.is_some() => {
+                format!("{} (unavailable)", work.abbreviation)
+            }
+            _ => String::new(),
+        }
+    }
+
+    fn chapter_text(&self) -> ChapterText {
+        match (self.current_work(), self.current_chapter()) {
+            (_, Some(chapter)) => chapter.text(&self.annotations),
+            (
+                Some(Work {
+                    error: Some(error), ..
+                }),
+                None,
+            ) => ChapterText {
+                text: Text::raw(format!("Unable to load {error}")),
+                ..Default::default()
+            },
+            _ => ChapterText::default(),
+        }
+    }
+
+    fn chapter_footnotes_text(&self) -> Vec<FootnoteText> {
+        self.current_chapter()
+            .map(|c| c.footnotes_text())
+            .unwrap_or_default()
+    }
+
+    /// The selected chapter rendered for the current size of the text pane.
+    /// The HTML is only parsed again when the selection or the pane width
+    /// changes.
+    pub fn rendered_chapter(&mut self) -> &RenderedChapter {
+        let key = RenderKey {
+            work: self.works_state.selected(),
+            book: self.books_state.selected(),
+            chapter: self.chapters_state.selected(),
+            text_width: self.text_rect.width,
+            reader_mode: self.reader_mode,
+        };
+
+        if self.rendered.as_ref().is_none_or(|r| r.key != key) {
+            let ChapterText {
+                mut text,
+                mut verses,
+            } = self.chapter_text();
+            add_note_markers(&mut text, &verses, &self.notes);
+            if self.reader_mode {
+                add_chapter_header(&mut text, &mut verses, &self.chapter_title());
+            }
+            let footnotes = self.chapter_footnotes_text();
+            self.rendered = Some(RenderedChapter {
+                key,
+                line_starts: wrapped_line_starts(&text, key.text_width),
+                line_count: wrapped_line_count(&text, key.text_width),
+                text,
+                verses,
+                footnotes,
+            });
+        }
+
+        self.rendered
+            .as_ref()
+            .expect("rendered chapter was just set")
     }
 
-    pub fn chapter_text(&self) -> Text {
-        let chapter = self.current_chapter();
-        chapter.text()
+    /// The chapter at `location`, rendered to go below the selected one in
+    /// reader mode.
+    fn reader_chapter(&mut self, location: Location) -> Option<&ReaderChapter> {
+        let text_width = self.text_rect.width;
+        if let Some(i) = self
+            .reader_chapters
+            .iter()
+            .position(|c| c.location == location && c.text_width == text_width)
+        {
+            return self.reader_chapters.get(i);
+        }
+
+        let work = self.data.works.get(location.work)?;
+        let entry = work
+            .books
+            .get(location.book)?
+            .chapters
+            .get(location.chapter)?;
+        let (work_uri, chapter_id, title) =
+            (work.uri.clone(), entry.id.clone(), entry.title.clone());
+        let chapter = match self
+            .data
+            .chapter(location.work, location.book, location.chapter)
+        {
+            Ok(chapter) => chapter?,
+            Err(e) => {
+                self.status = Some(format!("Unable to load chapter: {e}"));
+                return None;
+            }
+        };
+        let (annotations, notes) = match self.user_data() {
+            Some(user_data) => (
+                user_data
+                    .annotations(&work_uri, &chapter_id)
+                    .unwrap_or_default(),
+                user_data.notes(&work_uri, &chapter_id).unwrap_or_default(),
+            ),
+            None => (vec![], vec![]),
+        };
+
+        let ChapterText {
+            mut text,
+            mut verses,
+        } = chapter.text(&annotations);
+        add_note_markers(&mut text, &verses, &notes);
+        add_chapter_header(&mut text, &mut verses, &title);
+
+        if self.reader_chapters.len() >= READER_CACHE_CAPACITY {
+            self.reader_chapters.remove(0);
+        }
+        self.reader_chapters.push(ReaderChapter {
+            location,
+            text_width,
+            line_count: wrapped_line_count(&text, text_width),
+            text,
+        });
+        self.reader_chapters.last()
     }
 
-    pub fn chapter_footnotes_text(&self) -> Text {
-        let chapter = self.current_chapter();
-        let footnotes = chapter.footnotes_text();
-        footnotes
+    /// The chapters that fill the text pane below the selected one in reader
+    /// mode, with the number of rows each takes. They're rendered as they
+    /// come within a page of the bottom of the pane, so the next one is ready
+    /// before it's scrolled to.
+    pub fn following_chapters(&mut self) -> Vec<(Text<'static>, u16)> {
+        let height = self.text_rect.height;
+        let mut rows = self
+            .rendered_chapter()
+            .line_count
+            .saturating_sub(self.text_scroll);
+        let mut location = self.current_location();
+        let mut chapters = vec![];
+        while rows < height.saturating_mul(2) {
+            let Some(next) = location.and_then(|l| self.data.adjacent_chapter(l, true)) else {
+                break;
+            };
+            let Some(chapter) = self.reader_chapter(next) else {
+                break;
+            };
+            if rows < height {
+                chapters.push((chapter.text.clone(), chapter.line_count));
+            }
+            rows = rows.saturating_add(chapter.line_count.max(1));
+            location = Some(next);
+        }
+        chapters
     }
 
+    /// Titles for the Work column. Works that failed to load are marked unavailable.
     pub fn works_titles(&self) -> Vec<String> {
         self.data
             .works
             .iter()
-            .map(|w| w.title.clone())
+            .map(|w| match w.error {
+                Some(_) => format!("{} (unavailable)", w.abbreviation),
+                None => w.abbreviation.clone(),
+            })
             .collect::<Vec<_>>()
     }
 
+    pub fn work_available(&self, index: usize) -> bool {
+        self.data
+            .works
+            .get(index)
+            .is_some_and(|w| w.error.is_none())
+    }
+
     pub fn books_titles(&self) -> Vec<String> {
-        self.data.works[self.works_state.selected().unwrap_or_default()]
-            .books
-            .iter()
-            .map(|b| b.title.clone())
-            .collect::<Vec<_>>()
+        self.current_work()
+            .map(|w| w.books.iter().map(|b| b.title.clone()).collect::<Vec<_>>())
+            .unwrap_or_default()
     }
 
     pub fn chapters_titles(&self) -> Vec<String> {
-        self.data.works[self.works_state.selected().unwrap_or_default()].books
-            [self.books_state.selected().unwrap_or_default()]
-        .chapters
-        .iter()
-        .map(|c| c.title.clone())
-        .collect::<Vec<_>>()
+        self.current_book()
+            .map(|b| {
+                b.chapters
+                    .iter()
+                    .map(|c| c.title.clone())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
     }
 
     pub fn arrow_down(&mut self) {
@@ -509,7 +777,7 @@ impl App {
             0 => self.update_works(true),
             1 => self.update_books(true),
             2 => self.update_chapters(true),
-            _ => unreachable!(),
+            _ => self.scroll_pane(self.focused_pane(), Scroll::Line, true),
         }
     }
 
@@ -518,12 +786,15 @@ impl App {
             0 => self.update_works(false),
             1 => self.update_books(false),
             2 => self.update_chapters(false),
-            _ => unreachable!(),
+            _ => self.scroll_pane(self.focused_pane(), Scroll::Line, false),
         }
     }
 
     pub fn arrow_left(&mut self) {
-        if self.column_selected == 0 {
+        // Only the panes are shown in reader mode.
+        if self.reader_mode {
+            self.column_selected = TEXT_COLUMN + FOOTNOTE_COLUMN - self.column_selected;
+        } else if self.column_selected == 0 {
             self.column_selected = NUM_COLUMNS - 1;
         } else {
             self.column_selected -= 1;
@@ -531,119 +802,1540 @@ impl App {
     }
 
     pub fn arrow_right(&mut self) {
-        if self.column_selected == NUM_COLUMNS - 1 {
+        if self.reader_mode {
+            self.arrow_left();
+        } else if self.column_selected == NUM_COLUMNS - 1 {
             self.column_selected = 0;
         } else {
             self.column_selected += 1;
         }
     }
 
-    fn update_works(&mut self, down: bool) {
-        let i = if down {
-            match self.works_state.selected() {
-                Some(i) => {
-                    if i == self.data.works.len() - 1 {
-                        0
-                    } else {
-                        i + 1
-                    }
-                }
-                None => 0,
-            }
+    /// Selects the next or previous work in its list, wrapping around.
+    pub fn update_works(&mut self, down: bool) {
+        let i = step_selection(self.works_state.selected(), self.data.works.len(), down);
+        self.select_work(i);
+    }
+
+    fn select_work(&mut self, i: Option<usize>) {
+        self.browse(|app| {
+            app.works_state.select(i);
+
+            let first_book = app.current_work().and_then(|w| w.books.first()).map(|_| 0);
+            app.books_state = ListState::default().with_selected(first_book);
+            app.chapters_state = ListState::default().with_selected(first_book);
+            app.chapter_changed();
+        });
+    }
+
+    /// Selects the next or previous book in its list, wrapping around.
+    pub fn update_books(&mut self, down: bool) {
+        let len = self
+            .current_work()
+            .map(|w| w.books.len())
+            .unwrap_or_default();
+        let i = step_selection(self.books_state.selected(), len, down);
+        self.select_book(i);
+    }
+
+    fn select_book(&mut self, i: Option<usize>) {
+        self.browse(|app| {
+            app.books_state.select(i);
+            app.chapters_state = ListState::default().with_selected(i.map(|_| 0));
+            app.chapter_changed();
+        });
+    }
+
+    /// Selects the next or previous chapter in its list, wrapping around.
+    /// [`App::next_chapter`] goes on into other books instead.
+    pub fn update_chapters(&mut self, down: bool) {
+        let len = self
+            .current_book()
+            .map(|b| b.chapters.len())
+            .unwrap_or_default();
+        let i = step_selection(self.chapters_state.selected(), len, down);
+        self.select_chapter(i);
+    }
+
+    /// Goes to the next or previous chapter. From the last chapter of a book
+    /// it goes on to the first of the next book, and from the last book of a
+    /// work to the next work, rather than wrapping around. Returns whether
+    /// there was a chapter to go to.
+    pub fn next_chapter(&mut self, forward: bool) -> bool {
+        let Some(current) = self.current_location() else {
+            return false;
+        };
+        let Some(location) = self.data.adjacent_chapter(current, forward) else {
+            let end = if forward { "last" } else { "first" };
+            self.status = Some(format!("Already at the {end} chapter"));
+            return false;
+        };
+
+        self.remember_place();
+        if (location.work, location.book) == (current.work, current.book) {
+            self.chapters_state.select(Some(location.chapter));
+            self.chapter_changed();
         } else {
-            match self.works_state.selected() {
-                Some(i) => {
-                    if i == 0 {
-                        self.data.works.len() - 1
-                    } else {
-                        i - 1
-                    }
-                }
-                None => 0,
-            }
+            self.select(location);
+        }
+        true
+    }
+
+    fn select_chapter(&mut self, i: Option<usize>) {
+        self.browse(|app| {
+            app.chapters_state.select(i);
+            app.chapter_changed();
+        });
+    }
+
+    /// Runs `select` to move through the lists, recording where the reader
+    /// was unless they've done nothing but move through the lists since the
+    /// last time.
+    fn browse(&mut self, select: impl FnOnce(&mut Self)) {
+        let place = self.current_place();
+        if place.is_none() || place != self.browsed_to {
+            self.remember_place();
+        }
+        select(self);
+        self.browsed_to = self.current_place();
+    }
+
+    /// Selects the chapter at `location`.
+    pub fn select(&mut self, location: Location) {
+        self.works_state.select(Some(location.work));
+        self.books_state = ListState::default().with_selected(Some(location.book));
+        self.chapters_state = ListState::default().with_selected(Some(location.chapter));
+        self.chapter_changed();
+    }
+
+    /// Resizes the text pane. When its width changes the verse at the top
+    /// stays at the top, although the rows above it rewrap.
+    pub fn set_text_rect(&mut self, rect: Rect) {
+        let top_verse = if rect.width != self.text_rect.width && self.text_rect.width > 0 {
+            let text_scroll = self.text_scroll;
+            let rendered = self.rendered_chapter();
+            rendered
+                .verses
+                .iter()
+                .rposition(|v| rendered.verse_row(v) <= text_scroll)
+        } else {
+            None
         };
 
-        self.works_state.select(Some(i));
-        self.books_state = ListState::default().with_selected(Some(0));
-        self.chapters_state = ListState::default().with_selected(Some(0));
-        self.text_scroll = 0;
-        self.footnote_scroll = 0;
+        self.text_rect = rect;
+        if let Some(i) = top_verse {
+            let rendered = self.rendered_chapter();
+            self.text_scroll = rendered.verse_row(&rendered.verses[i]);
+        }
     }
 
-    fn update_books(&mut self, down: bool) {
-        let i = if down {
-            match self.books_state.selected() {
-                Some(i) => {
-                    if i == self.data.works[self.works_state.selected().unwrap_or_default()]
-                        .books
-                        .len()
-                        - 1
-                    {
-                        0
-                    } else {
-                        i + 1
-                    }
+    /// The pane keyboard scrolling applies to: the focused one, or the text
+    /// pane while a list is focused.
+    pub fn focused_pane(&self) -> Pane {
+        match self.column_selected {
+            FOOTNOTE_COLUMN => Pane::Footnotes,
+            _ => Pane::Text,
+        }
+    }
+
+    /// The pane at `column`, `row` of the terminal, if any.
+    pub fn pane_at(&self, column: u16, row: u16) -> Option<Pane> {
+        let position = Position::new(column, row);
+        if self.text_rect.contains(position) {
+            Some(Pane::Text)
+        } else if self.footnote_rect.contains(position) {
+            Some(Pane::Footnotes)
+        } else {
+            None
+        }
+    }
+
+    /// Scrolls `pane` down or up, no further than the end of its text.
+    pub fn scroll_pane(&mut self, pane: Pane, scroll: Scroll, down: bool) {
+        let (offset, height, max) = match pane {
+            Pane::Text => (
+                self.text_scroll,
+                self.text_rect.height,
+                self.max_text_scroll(),
+            ),
+            Pane::Footnotes => (
+                self.footnote_scroll,
+                self.footnote_rect.height,
+                self.max_footnote_scroll(),
+            ),
+        };
+        let rows = match scroll {
+            Scroll::Line => 1,
+            Scroll::HalfPage => (height / 2).max(1),
+            Scroll::Page => height.saturating_sub(1).max(1),
+            Scroll::End => u16::MAX,
+        };
+
+        // In reader mode the text pane scrolls through the chapters, with the
+        // top and bottom being those of the selected chapter.
+        if pane == Pane::Text && self.reader_mode {
+            match scroll {
+                Scroll::End if down => {
+                    let line_count = self.rendered_chapter().line_count;
+                    self.text_scroll = line_count.saturating_sub(height);
                 }
-                None => 0,
+                Scroll::End => self.text_scroll = 0,
+                _ => self.scroll_reader(rows, down),
+            }
+            return;
+        }
+
+        // Scrolling on from the end of the chapter continues into the next
+        // one, and from the top into the end of the previous one.
+        if pane == Pane::Text
+            && self.auto_continue
+            && scroll != Scroll::End
+            && offset == if down { max } else { 0 }
+        {
+            if self.next_chapter(down) && !down {
+                self.text_scroll = self.max_text_scroll();
             }
+            return;
+        }
+
+        let offset = if down {
+            offset.saturating_add(rows).min(max)
         } else {
-            match self.books_state.selected() {
-                Some(i) => {
-                    if i == 0 {
-                        self.data.works[self.works_state.selected().unwrap_or_default()]
-                            .books
-                            .len()
-                            - 1
-                    } else {
-                        i - 1
-                    }
+            offset.saturating_sub(rows)
+        };
+
+        match pane {
+            Pane::Text => self.text_scroll = offset,
+            Pane::Footnotes => self.footnote_scroll = offset,
+        }
+    }
+
+    /// Handles a click at `column`, `row` of the terminal, focusing what was
+    /// clicked. A list row is selected, a verse gets the cursor, and a
+    /// footnote marker has its note shown.
+    pub fn click(&mut self, column: u16, row: u16) {
+        let position = Position::new(column, row);
+        if let Some(list) = self.list_rects.iter().position(|r| r.contains(position)) {
+            self.column_selected = list;
+            self.click_list(list, (row - self.list_rects[list].y) as usize);
+            return;
+        }
+
+        match self.pane_at(column, row) {
+            Some(Pane::Text) => {
+                self.column_selected = TEXT_COLUMN;
+                let Some((verse, span)) = self.verse_at(column, row) else {
+                    return;
+                };
+                self.drag_start = Some(verse);
+                self.set_verse_cursor(verse, None);
+
+                let marker = self.cursor_verse().and_then(|v| {
+                    span.and_then(|span| v.markers.iter().position(|m| m.span == span))
+                });
+                if marker.is_some() {
+                    self.selected_footnote = marker;
                 }
-                None => 0,
             }
+            Some(Pane::Footnotes) => self.column_selected = FOOTNOTE_COLUMN,
+            None => {}
+        }
+    }
+
+    /// Selects the row `offset` rows below the top of list `list` as it's
+    /// scrolled.
+    fn click_list(&mut self, list: usize, offset: usize) {
+        let (state, len) = match list {
+            0 => (&self.works_state, self.data.works.len()),
+            1 => (
+                &self.books_state,
+                self.current_work()
+                    .map(|w| w.books.len())
+                    .unwrap_or_default(),
+            ),
+            _ => (
+                &self.chapters_state,
+                self.current_book()
+                    .map(|b| b.chapters.len())
+                    .unwrap_or_default(),
+            ),
         };
+        let i = state.offset() + offset;
+        if i >= len || state.selected() == Some(i) {
+            return;
+        }
 
-        self.books_state.select(Some(i));
-        self.chapters_state = ListState::default().with_selected(Some(0));
-        self.text_scroll = 0;
-        self.footnote_scroll = 0;
+        match list {
+            0 => self.select_work(Some(i)),
+            1 => self.select_book(Some(i)),
+            _ => self.select_chapter(Some(i)),
+        }
     }
 
-    fn update_chapters(&mut self, down: bool) {
-        let i = if down {
-            match self.chapters_state.selected() {
-                Some(i) => {
-                    if i == self.data.works[self.works_state.selected().unwrap_or_default()].books
-                        [self.books_state.selected().unwrap_or_default()]
-                    .chapters
-                    .len()
-                        - 1
-                    {
-                        0
-                    } else {
-                        i + 1
+    /// While dragging from a verse, selects the verses from there to the one
+    /// at `column`, `row`, scrolling when the mouse is above or below the text
+    /// pane.
+    pub fn drag(&mut self, column: u16, row: u16) {
+        let Some(start) = self.drag_start else {
+            return;
+        };
+        let rect = self.text_rect;
+        if row < rect.top() {
+            self.scroll_pane(Pane::Text, Scroll::Line, false);
+        } else if row >= rect.bottom() {
+            self.scroll_pane(Pane::Text, Scroll::Line, true);
+        }
+
+        let column = column.clamp(rect.left(), rect.right().saturating_sub(1));
+        let row = row.clamp(rect.top(), rect.bottom().saturating_sub(1));
+        if let Some((verse, _)) = self.verse_at(column, row) {
+            let anchor = (verse != start).then_some(start);
+            self.set_verse_cursor(verse, anchor);
+        }
+    }
+
+    /// Ends a drag started by [`App::click`].
+    pub fn end_drag(&mut self) {
+        self.drag_start = None;
+    }
+
+    /// The verse shown at `column`, `row` of the terminal, and the index of
+    /// the span of its line there, if any.
+    fn verse_at(&mut self, column: u16, row: u16) -> Option<(usize, Option<usize>)> {
+        let rect = self.text_rect;
+        let text_row = self.text_scroll + row.checked_sub(rect.y)?;
+        let rendered = self.rendered_chapter();
+        let (line, line_row) = rendered.line_at_row(text_row)?;
+        let verse = rendered.verses.iter().position(|v| v.line == line)?;
+        let span = span_at(
+            &rendered.text.lines[line],
+            rect.width,
+            line_row,
+            column.checked_sub(rect.x)?,
+        );
+        Some((verse, span))
+    }
+
+    /// Puts the verse cursor on verse `index`, with the selection extending to
+    /// `anchor`, and scrolls it into view.
+    fn set_verse_cursor(&mut self, index: usize, anchor: Option<usize>) {
+        if self.verse_cursor != Some(index) {
+            self.selected_footnote = None;
+            self.selected_link = None;
+            self.footnote_scroll = 0;
+        }
+        self.verse_cursor = Some(index);
+        self.verse_anchor = anchor;
+        self.scroll_to_cursor();
+    }
+
+    /// Keeps both panes from scrolling past the end of their text, such as
+    /// after the terminal grows or a saved scroll offset is restored.
+    pub fn clamp_scroll(&mut self) {
+        self.text_scroll = self.text_scroll.min(self.max_text_scroll());
+        self.footnote_scroll = self.footnote_scroll.min(self.max_footnote_scroll());
+    }
+
+    /// Furthest the text pane can scroll before running out of text. In
+    /// reader mode that's the chapter's last row while another chapter
+    /// follows it.
+    fn max_text_scroll(&mut self) -> u16 {
+        let height = self.text_rect.height;
+        let line_count = self.rendered_chapter().line_count;
+        let continues = self.reader_mode
+            && self
+                .current_location()
+                .and_then(|l| self.data.adjacent_chapter(l, true))
+                .is_some();
+        if continues {
+            line_count.saturating_sub(1)
+        } else {
+            line_count.saturating_sub(height)
+        }
+    }
+
+    /// Scrolls the chapters of reader mode by `rows`. Whichever chapter ends
+    /// up at the top of the text pane is selected.
+    fn scroll_reader(&mut self, rows: u16, down: bool) {
+        let mut rows = rows;
+        loop {
+            let line_count = self.rendered_chapter().line_count;
+            let adjacent = self
+                .current_location()
+                .and_then(|l| self.data.adjacent_chapter(l, down));
+            if down {
+                let target = self.text_scroll.saturating_add(rows);
+                match adjacent {
+                    Some(next) if target >= line_count => {
+                        rows = target - line_count;
+                        self.select(next);
+                    }
+                    _ => {
+                        self.text_scroll = target.min(self.max_text_scroll());
+                        return;
                     }
                 }
-                None => 0,
-            }
-        } else {
-            match self.chapters_state.selected() {
-                Some(i) => {
-                    if i == 0 {
-                        self.data.works[self.works_state.selected().unwrap_or_default()].books
-                            [self.books_state.selected().unwrap_or_default()]
-                        .chapters
-                        .len()
-                            - 1
-                    } else {
-                        i - 1
+            } else {
+                match adjacent {
+                    Some(previous) if rows > self.text_scroll => {
+                        rows -= self.text_scroll;
+                        self.select(previous);
+                        self.text_scroll = self.rendered_chapter().line_count;
+                    }
+                    _ => {
+                        self.text_scroll = self.text_scroll.saturating_sub(rows);
+                        return;
                     }
                 }
-                None => 0,
             }
-        };
-
-        self.chapters_state.select(Some(i));
-        self.text_scroll = 0;
+        }
+    }
+
+    /// Shows the chapters one after another without the lists, or goes back
+    /// to showing the lists.
+    pub fn toggle_reader_mode(&mut self) {
+        self.reader_mode = !self.reader_mode;
+        self.reader_chapters.clear();
+        if self.reader_mode {
+            self.column_selected = self.column_selected.max(TEXT_COLUMN);
+        }
+
+        // Keep the same text at the top, now that the chapter header is
+        // shown or hidden above it, unless that's the top of the chapter.
+        let header_rows = CHAPTER_HEADER_LINES as u16;
+        if self.reader_mode && self.text_scroll > 0 {
+            self.text_scroll = self.text_scroll.saturating_add(header_rows);
+        } else if !self.reader_mode {
+            self.text_scroll = self.text_scroll.saturating_sub(header_rows);
+        }
+    }
+
+    /// Puts the cursor on verse `number` and scrolls it to the top of the
+    /// text pane.
+    pub fn scroll_to_verse(&mut self, number: u32) {
+        let verse = self
+            .rendered_chapter()
+            .verses
+            .iter()
+            .position(|v| v.number == Some(number));
+
+        match verse {
+            Some(i) => self.scroll_to_verse_index(i),
+            None => self.status = Some(format!("No verse {number} in this chapter")),
+        }
+    }
+
+    /// Puts the cursor on the verse at `index` in [`RenderedChapter::verses`]
+    /// and scrolls it to the top of the text pane.
+    fn scroll_to_verse_index(&mut self, index: usize) {
+        let rendered = self.rendered_chapter();
+        let Some(verse) = rendered.verses.get(index) else {
+            return;
+        };
+
+        let row = rendered.verse_row(verse);
+        self.verse_cursor = Some(index);
+        self.verse_anchor = None;
+        self.selected_footnote = None;
+        self.selected_link = None;
         self.footnote_scroll = 0;
+        self.text_scroll = row.min(self.max_text_scroll());
+    }
+
+    /// Verses between the cursor and the selection anchor, inclusive.
+    pub fn selected_verses(&self) -> Option<RangeInclusive<usize>> {
+        let cursor = self.verse_cursor?;
+        let anchor = self.verse_anchor.unwrap_or(cursor);
+        Some(cursor.min(anchor)..=cursor.max(anchor))
+    }
+
+    /// Moves the verse cursor to the next or previous verse. When `extend` is
+    /// set the selection grows from where it started instead of collapsing.
+    pub fn move_verse_cursor(&mut self, down: bool, extend: bool) {
+        let text_scroll = self.text_scroll;
+        let current = self.verse_cursor;
+
+        // In reader mode the cursor carries on into the next or previous
+        // chapter.
+        let len = self.rendered_chapter().verses.len();
+        let at_end = match current {
+            Some(i) if down => i + 1 >= len,
+            Some(i) => i == 0,
+            None => len == 0,
+        };
+        if self.reader_mode && !extend && at_end {
+            let adjacent = self
+                .current_location()
+                .and_then(|l| self.data.adjacent_chapter(l, down));
+            if let Some(location) = adjacent {
+                self.select(location);
+                let len = self.rendered_chapter().verses.len();
+                if len > 0 {
+                    self.set_verse_cursor(if down { 0 } else { len - 1 }, None);
+                }
+            }
+            return;
+        }
+
+        let rendered = self.rendered_chapter();
+        let len = rendered.verses.len();
+        if len == 0 {
+            return;
+        }
+
+        let cursor = match current {
+            Some(i) if down => (i + 1).min(len - 1),
+            Some(i) => i.saturating_sub(1),
+            // Start from the first verse on screen.
+            None => rendered
+                .verses
+                .iter()
+                .position(|v| rendered.verse_row(v) >= text_scroll)
+                .unwrap_or(len - 1),
+        };
+
+        let anchor = if extend {
+            self.verse_anchor.or(self.verse_cursor)
+        } else {
+            None
+        };
+        self.set_verse_cursor(cursor, anchor);
+    }
+
+    /// Deselects the footnote, or collapses the verse selection, or closes
+    /// the note panel, whichever comes first. Returns whether there was
+    /// anything to do.
+    pub fn clear(&mut self) -> bool {
+        self.clear_footnote_selection() || self.clear_verse_selection() || self.close_note_panel()
+    }
+
+    /// Names the keys that move the verse cursor, as in " with j/k", or
+    /// nothing when they aren't both bound.
+    fn verse_keys_hint(&self) -> String {
+        let next = self.keymap.key_for(Action::NextVerse);
+        let previous = self.keymap.key_for(Action::PrevVerse);
+        match next.zip(previous) {
+            Some((next, previous)) => format!(" with {next}/{previous}"),
+            None => String::new(),
+        }
+    }
+
+    /// Collapses a multi-verse selection to the cursor. Returns whether there
+    /// was anything to collapse.
+    pub fn clear_verse_selection(&mut self) -> bool {
+        self.verse_anchor.take().is_some()
+    }
+
+    /// Scrolls the text pane just enough for the cursor's verse to be visible.
+    fn scroll_to_cursor(&mut self) {
+        let Some(cursor) = self.verse_cursor else {
+            return;
+        };
+        let height = self.text_rect.height;
+        let rendered = self.rendered_chapter();
+        let Some(verse) = rendered.verses.get(cursor) else {
+            return;
+        };
+
+        let top = rendered.verse_row(verse);
+        let bottom = rendered
+            .line_starts
+            .get(verse.line + 1)
+            .copied()
+            .unwrap_or(rendered.line_count);
+        if top < self.text_scroll {
+            self.text_scroll = top;
+        } else if bottom > self.text_scroll + height {
+            self.text_scroll = bottom.saturating_sub(height).min(top);
+        }
+    }
+
+    /// The verse the cursor is on.
+    pub fn cursor_verse(&mut self) -> Option<&Verse> {
+        let cursor = self.verse_cursor?;
+        self.rendered_chapter().verses.get(cursor)
+    }
+
+    /// Footnotes shown in the footnote pane: the one selected marker's note,
+    /// otherwise every note of the cursor's verse, otherwise the whole chapter.
+    fn visible_footnotes(&mut self) -> Vec<FootnoteText> {
+        let selected = self.selected_footnote;
+        let verse = self.cursor_verse().cloned();
+        let footnotes = &self.rendered_chapter().footnotes;
+
+        let Some(verse) = verse else {
+            return footnotes.clone();
+        };
+        let refs = match selected.and_then(|i| verse.markers.get(i)) {
+            Some(marker) => vec![marker.ref_id.as_str()],
+            None => verse.markers.iter().map(|m| m.ref_id.as_str()).collect(),
+        };
+
+        refs.iter()
+            .filter_map(|ref_id| {
+                footnotes
+                    .iter()
+                    .find(|f| f.ref_id == *ref_id && f.origin_id == verse.id)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Content of the footnote pane.
+    pub fn footnote_pane_text(&mut self) -> Text<'static> {
+        let mut remaining = self.selected_link;
+        let mut lines = vec![];
+        for footnote in self.visible_footnotes() {
+            let mut line = footnote.line;
+            if let Some(i) = remaining {
+                match footnote.links.get(i) {
+                    Some(link) => {
+                        if let Some(span) = line.spans.get_mut(link.span) {
+                            span.style = span.style.add_modifier(Modifier::REVERSED);
+                        }
+                        remaining = None;
+                    }
+                    None => remaining = Some(i - footnote.links.len()),
+                }
+            }
+            lines.push(line);
+        }
+        Text::from(lines)
+    }
+
+    /// Title of the footnote pane, naming the note being read, if any.
+    pub fn footnote_pane_title(&mut self) -> String {
+        match self.selected_footnote {
+            Some(_) => match self.visible_footnotes().first() {
+                Some(footnote) => format!("Footnote {}", footnote.label),
+                None => "Footnotes".to_string(),
+            },
+            None => "Footnotes".to_string(),
+        }
+    }
+
+    /// Furthest the footnote pane can scroll before running out of text.
+    pub fn max_footnote_scroll(&mut self) -> u16 {
+        let text = self.footnote_pane_text();
+        wrapped_line_count(&text, self.footnote_rect.width)
+            .saturating_sub(self.footnote_rect.height)
+    }
+
+    /// Selects the next or previous footnote marker in the cursor's verse, so
+    /// its note is shown on its own. Puts the cursor on the first verse on
+    /// screen if it isn't anywhere yet.
+    pub fn move_footnote_selection(&mut self, forward: bool) {
+        if self.verse_cursor.is_none() {
+            self.move_verse_cursor(true, false);
+        }
+        let Some(count) = self.cursor_verse().map(|v| v.markers.len()) else {
+            return;
+        };
+        if count == 0 {
+            self.status = Some("No footnotes in this verse".to_string());
+            return;
+        }
+
+        self.selected_footnote = Some(match self.selected_footnote {
+            Some(i) if forward => (i + 1) % count,
+            Some(0) => count - 1,
+            Some(i) => i - 1,
+            None if forward => 0,
+            None => count - 1,
+        });
+        self.selected_link = None;
+        self.footnote_scroll = 0;
+    }
+
+    /// Goes back to showing every footnote of the verse. Returns whether a
+    /// footnote was selected.
+    pub fn clear_footnote_selection(&mut self) -> bool {
+        self.footnote_scroll = 0;
+        self.selected_link = None;
+        self.selected_footnote.take().is_some()
+    }
+
+    /// Cross-references in the footnotes shown, in order.
+    fn visible_links(&mut self) -> Vec<Link> {
+        self.visible_footnotes()
+            .into_iter()
+            .flat_map(|f| f.links)
+            .collect()
+    }
+
+    /// Selects the next or previous cross-reference in the footnote pane,
+    /// scrolling the pane to the footnote it's in.
+    pub fn move_link_selection(&mut self, forward: bool) {
+        let footnotes = self.visible_footnotes();
+        let count = footnotes.iter().map(|f| f.links.len()).sum::<usize>();
+        if count == 0 {
+            self.status = Some("No cross-references in these footnotes".to_string());
+            return;
+        }
+
+        let selected = match self.selected_link {
+            Some(i) if forward => (i + 1) % count,
+            Some(0) => count - 1,
+            Some(i) => i - 1,
+            None if forward => 0,
+            None => count - 1,
+        };
+        self.selected_link = Some(selected);
+
+        // Line of the footnote the link is in.
+        let mut line = 0;
+        let mut remaining = selected;
+        for footnote in &footnotes {
+            if remaining < footnote.links.len() {
+                break;
+            }
+            remaining -= footnote.links.len();
+            line += 1;
+        }
+
+        let text = self.footnote_pane_text();
+        let rect = self.footnote_rect;
+        let row = wrapped_line_starts(&text, rect.width)
+            .get(line)
+            .copied()
+            .unwrap_or_default();
+        let next_row = wrapped_line_starts(&text, rect.width)
+            .get(line + 1)
+            .copied()
+            .unwrap_or_else(|| wrapped_line_count(&text, rect.width));
+        if row < self.footnote_scroll || next_row > self.footnote_scroll + rect.height {
+            self.footnote_scroll = row.min(self.max_footnote_scroll());
+        }
+    }
+
+    /// Jumps to the passage the selected cross-reference points to.
+    pub fn follow_link(&mut self) {
+        let Some(link) = self
+            .selected_link
+            .and_then(|i| self.visible_links().into_iter().nth(i))
+        else {
+            return;
+        };
+
+        let Some(location) = self.data.locate_uri(&link.uri) else {
+            self.status = Some(format!("{} isn't in the loaded scriptures", link.label));
+            return;
+        };
+
+        self.remember_place();
+        self.status = None;
+        self.select(location);
+
+        let verses = &self.rendered_chapter().verses;
+        let position = |id: &Option<String>| {
+            id.as_ref()
+                .and_then(|id| verses.iter().position(|v| v.id == *id))
+        };
+        let (first, last) = (position(&link.first_verse), position(&link.last_verse));
+        if let Some(first) = first {
+            self.scroll_to_verse_index(first);
+            if last.is_some_and(|last| last != first) {
+                self.verse_anchor = last;
+            }
+        }
+    }
+
+    /// The selected chapter.
+    fn current_location(&self) -> Option<Location> {
+        Some(Location {
+            work: self.works_state.selected()?,
+            book: self.books_state.selected()?,
+            chapter: self.chapters_state.selected()?,
+        })
+    }
+
+    /// Where the reader is now.
+    fn current_place(&self) -> Option<Place> {
+        Some(Place {
+            location: self.current_location()?,
+            text_scroll: self.text_scroll,
+            verse_cursor: self.verse_cursor,
+            verse_anchor: self.verse_anchor,
+        })
+    }
+
+    /// Records where the reader is before going somewhere else.
+    fn remember_place(&mut self) {
+        if let Some(place) = self.current_place() {
+            self.history.push(place);
+        }
+    }
+
+    /// Returns to `place`, as it was left.
+    fn restore(&mut self, place: Place) {
+        self.browsed_to = None;
+        self.select(place.location);
+        self.text_scroll = place.text_scroll.min(self.max_text_scroll());
+        self.verse_cursor = place.verse_cursor;
+        self.verse_anchor = place.verse_anchor;
+    }
+
+    /// Goes back to the previous place.
+    pub fn go_back(&mut self) {
+        let Some(current) = self.current_place() else {
+            return;
+        };
+        match self.history.back(current) {
+            Some(place) => self.restore(place),
+            None => self.status = Some("Already at the oldest place".to_string()),
+        }
+    }
+
+    /// Undoes [`App::go_back`].
+    pub fn go_forward(&mut self) {
+        let Some(current) = self.current_place() else {
+            return;
+        };
+        match self.history.forward(current) {
+            Some(place) => self.restore(place),
+            None => self.status = Some("Already at the newest place".to_string()),
+        }
+    }
+
+    /// Jumps to the chapter and verse named by a reference such as `Alma 32:21`.
+    pub fn goto(&mut self, input: &str) {
+        let reference = match Reference::parse(input) {
+            Ok(reference) => reference,
+            Err(e) => {
+                self.status = Some(format!("{input}: {e}"));
+                return;
+            }
+        };
+
+        let location = match reference.resolve(&self.data) {
+            Ok(location) => location,
+            Err(e) => {
+                self.status = Some(e.to_string());
+                return;
+            }
+        };
+
+        self.remember_place();
+        self.select(location);
+        if let Some(verse) = reference.first_verse() {
+            self.scroll_to_verse(verse);
+
+            // Select the whole of the first range, such as 7-8 in D&C 121:7-8.
+            let last = reference.verses.first().map(|r| *r.end());
+            let rendered = self.rendered_chapter();
+            let anchor = rendered.verses.iter().position(|v| v.number == last);
+            if anchor != self.verse_cursor {
+                self.verse_anchor = anchor;
+            }
+        }
+    }
+
+    /// Reopens the chapter and position saved by the last session. Anything
+    /// that no longer exists, such as a work that was uninstalled, is left
+    /// where [`App::new`] put it.
+    pub fn restore_session(&mut self, state: &SessionState) {
+        self.column_selected = state.column_selected.min(NUM_COLUMNS - 1);
+        self.reader_mode = state.reader_mode;
+        if self.reader_mode {
+            self.column_selected = self.column_selected.max(TEXT_COLUMN);
+        }
+
+        let location = state
+            .work_uri
+            .as_deref()
+            .zip(state.chapter_id.as_deref())
+            .and_then(|(work_uri, chapter_id)| self.data.locate(work_uri, chapter_id));
+        let Some(location) = location else {
+            return;
+        };
+        self.select(location);
+
+        self.text_scroll = state.text_scroll;
+        self.footnote_scroll = state.footnote_scroll;
+        if let Some(id) = &state.verse_id {
+            self.verse_cursor = self
+                .rendered_chapter()
+                .verses
+                .iter()
+                .position(|v| v.id == *id);
+        }
+    }
+
+    /// What to save for the next session to restore.
+    pub fn session_state(&mut self) -> SessionState {
+        let chapter_id = self.current_chapter_entry().map(|c| c.id.clone());
+        SessionState {
+            work_uri: self.current_work().map(|w| w.uri.clone()),
+            chapter_id,
+            verse_id: self.cursor_verse().map(|v| v.id.clone()),
+            text_scroll: self.text_scroll,
+            footnote_scroll: self.footnote_scroll,
+            column_selected: self.column_selected,
+            reader_mode: self.reader_mode,
+        }
+    }
+
+    /// Starts typing into the status line.
+    pub fn open_prompt(&mut self, kind: PromptKind) {
+        self.status = None;
+        self.mode = Mode::Prompt(Prompt {
+            kind,
+            input: String::new(),
+        });
+    }
+
+    /// The prompt being typed into, if any.
+    pub fn prompt_mut(&mut self) -> Option<&mut Prompt> {
+        match &mut self.mode {
+            Mode::Prompt(prompt) => Some(prompt),
+            _ => None,
+        }
+    }
+
+    /// Closes the prompt without acting on it.
+    pub fn cancel_prompt(&mut self) {
+        self.mode = Mode::Normal;
+    }
+
+    /// Closes the prompt and acts on what was typed.
+    pub fn submit_prompt(&mut self) {
+        let Mode::Prompt(prompt) = std::mem::take(&mut self.mode) else {
+            return;
+        };
+
+        match prompt.kind {
+            PromptKind::Goto => self.goto(&prompt.input),
+            PromptKind::Search => self.search(&prompt.input),
+            PromptKind::Bookmark => self.add_bookmark(&prompt.input),
+            PromptKind::Annotate(kind) => self.annotate(kind, &prompt.input),
+        }
+    }
+
+    /// Searches every loaded work for `query` and lists the matching verses.
+    pub fn search(&mut self, query: &str) {
+        let Some(index) = &self.search_index else {
+            match &mut self.indexing {
+                Some((_, pending)) => *pending = query.to_string(),
+                None => {
+                    let receiver = SearchIndex::open_in_background(&self.data.data_dir);
+                    self.indexing = Some((receiver, query.to_string()));
+                }
+            }
+            self.status = Some(format!("Indexing the scriptures to search for {query}…"));
+            return;
+        };
+
+        match index.search(query) {
+            Ok(results) if results.is_empty() => {
+                self.status = Some(format!("No matches for {query}"));
+            }
+            Ok(results) => {
+                self.status = Some(format!("{} matches for {query}", results.len()));
+                self.search_results = results;
+                self.search_results_state = ListState::default().with_selected(Some(0));
+                self.mode = Mode::SearchResults;
+            }
+            Err(e) => self.status = Some(format!("Search failed: {e}")),
+        }
+    }
+
+    /// Takes the search index once it has been opened in the background and
+    /// searches for the query that was waiting for it.
+    fn finish_indexing(&mut self) {
+        let Some((receiver, _)) = &self.indexing else {
+            return;
+        };
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => Err("Indexing stopped".to_string()),
+        };
+        let Some((_, query)) = self.indexing.take() else {
+            return;
+        };
+
+        match result {
+            Ok(index) => {
+                self.search_index = Some(index);
+                // Don't pull the reader out of whatever they've gone on to do.
+                if matches!(self.mode, Mode::Normal) {
+                    self.search(&query);
+                } else {
+                    self.status = Some(format!("Search index ready, search again for {query}"));
+                }
+            }
+            Err(e) => self.status = Some(format!("Unable to open search index: {e}")),
+        }
+    }
+
+    /// Moves the highlighted search result up or down.
+    pub fn move_search_selection(&mut self, down: bool) {
+        let i = step_selection(
+            self.search_results_state.selected(),
+            self.search_results.len(),
+            down,
+        );
+        self.search_results_state.select(i);
+    }
+
+    /// Goes to the highlighted search result.
+    pub fn open_search_result(&mut self) {
+        let Some(result) = self
+            .search_results_state
+            .selected()
+            .and_then(|i| self.search_results.get(i))
+            .cloned()
+        else {
+            return;
+        };
+
+        self.mode = Mode::Normal;
+        match self.data.locate(&result.work_uri, &result.chapter_id) {
+            Some(location) => {
+                self.status = None;
+                self.remember_place();
+                self.select(location);
+                if let Some(verse) = result.verse {
+                    self.scroll_to_verse(verse);
+                }
+            }
+            None => self.status = Some(format!("{} is no longer available", result.reference)),
+        }
+    }
+
+    /// Hides the search results.
+    pub fn close_search_results(&mut self) {
+        self.mode = Mode::Normal;
+    }
+
+    /// The user database, opening it if this is the first use.
+    fn user_data(&mut self) -> Option<&UserData> {
+        if self.user_data.is_none() {
+            match UserData::open() {
+                Ok(user_data) => self.user_data = Some(user_data),
+                Err(e) => {
+                    self.status = Some(format!("Unable to open user data: {e}"));
+                    return None;
+                }
+            }
+        }
+        self.user_data.as_ref()
+    }
+
+    /// Asks which list to bookmark the cursor's verse in, or the chapter when
+    /// there's no cursor. Suggests the list last added to.
+    pub fn start_bookmark(&mut self) {
+        if self.current_chapter_entry().is_none() {
+            return;
+        }
+        let list = match self.user_data().map(|u| u.last_list()) {
+            Some(Ok(list)) => list.unwrap_or_else(|| user_data::DEFAULT_LIST.to_string()),
+            Some(Err(e)) => {
+                self.status = Some(format!("Unable to read bookmarks: {e}"));
+                return;
+            }
+            None => return,
+        };
+
+        self.open_prompt(PromptKind::Bookmark);
+        if let Some(prompt) = self.prompt_mut() {
+            prompt.input = list;
+        }
+    }
+
+    /// Bookmarks the cursor's verse, or the chapter when there's no cursor,
+    /// in the list named `list`.
+    fn add_bookmark(&mut self, list: &str) {
+        let list = match list.trim() {
+            "" => user_data::DEFAULT_LIST,
+            list => list,
+        };
+        let verse = self.cursor_verse().cloned();
+        let (Some(work), Some(entry)) = (self.current_work(), self.current_chapter_entry()) else {
+            return;
+        };
+
+        let label = verse_label(&entry.title, verse.as_ref());
+        let bookmark = Bookmark {
+            id: 0,
+            list: list.to_string(),
+            work_uri: work.uri.clone(),
+            chapter_id: entry.id.clone(),
+            verse_aid: verse.map(|v| v.aid).filter(|aid| !aid.is_empty()),
+            label,
+        };
+
+        self.status = match self.user_data().map(|u| u.add_bookmark(&bookmark)) {
+            Some(Ok(())) => Some(format!("Bookmarked {} in {list}", bookmark.label)),
+            Some(Err(e)) => Some(format!("Unable to add bookmark: {e}")),
+            None => return,
+        };
+    }
+
+    /// Shows every bookmark to choose one to go to.
+    pub fn open_bookmarks(&mut self) {
+        let bookmarks = match self.user_data().map(|u| u.bookmarks()) {
+            Some(Ok(bookmarks)) => bookmarks,
+            Some(Err(e)) => {
+                self.status = Some(format!("Unable to read bookmarks: {e}"));
+                return;
+            }
+            None => return,
+        };
+        if bookmarks.is_empty() {
+            self.status = Some(match self.keymap.key_for(Action::Bookmark) {
+                Some(key) => format!("No bookmarks yet. Press {key} to add one"),
+                None => "No bookmarks yet".to_string(),
+            });
+            return;
+        }
+
+        self.bookmarks = bookmarks;
+        // The first row is the first list's heading.
+        self.bookmarks_state = ListState::default().with_selected(Some(1));
+        self.mode = Mode::Bookmarks;
+    }
+
+    /// The rows of the bookmarks panel: each list's name followed by its
+    /// bookmarks.
+    pub fn bookmark_rows(&self) -> Vec<BookmarkRow<'_>> {
+        let mut rows = vec![];
+        for (i, bookmark) in self.bookmarks.iter().enumerate() {
+            if i == 0 || self.bookmarks[i - 1].list != bookmark.list {
+                rows.push(BookmarkRow::List(&bookmark.list));
+            }
+            rows.push(BookmarkRow::Bookmark(i));
+        }
+        rows
+    }
+
+    /// The bookmark on the selected row.
+    fn selected_bookmark(&self) -> Option<&Bookmark> {
+        let row = self.bookmarks_state.selected()?;
+        match self.bookmark_rows().get(row)? {
+            BookmarkRow::Bookmark(i) => self.bookmarks.get(*i),
+            BookmarkRow::List(_) => None,
+        }
+    }
+
+    /// Moves the selection to the next or previous bookmark, skipping over
+    /// list headings and wrapping around at either end.
+    pub fn move_bookmark_selection(&mut self, down: bool) {
+        let rows = self.bookmark_rows();
+        let mut selected = self.bookmarks_state.selected();
+        for _ in 0..rows.len() {
+            selected = step_selection(selected, rows.len(), down);
+            if let Some(BookmarkRow::Bookmark(_)) = selected.map(|i| rows[i]) {
+                break;
+            }
+        }
+        self.bookmarks_state.select(selected);
+    }
+
+    /// Goes to the selected bookmark.
+    pub fn open_bookmark(&mut self) {
+        let Some(bookmark) = self.selected_bookmark().cloned() else {
+            return;
+        };
+
+        self.mode = Mode::Normal;
+        let Some(location) = self.data.locate(&bookmark.work_uri, &bookmark.chapter_id) else {
+            self.status = Some(format!("{} is no longer available", bookmark.label));
+            return;
+        };
+
+        self.status = None;
+        self.remember_place();
+        self.select(location);
+        if let Some(aid) = &bookmark.verse_aid {
+            let verse = self
+                .rendered_chapter()
+                .verses
+                .iter()
+                .position(|v| v.aid == *aid);
+            match verse {
+                Some(i) => self.scroll_to_verse_index(i),
+                None => self.status = Some(format!("{} is no longer available", bookmark.label)),
+            }
+        }
+    }
+
+    /// Deletes the selected bookmark.
+    pub fn delete_bookmark(&mut self) {
+        let Some(bookmark) = self.selected_bookmark().cloned() else {
+            return;
+        };
+        if let Some(Err(e)) = self.user_data().map(|u| u.delete_bookmark(bookmark.id)) {
+            self.status = Some(format!("Unable to delete bookmark: {e}"));
+            return;
+        }
+
+        self.status = Some(format!("Deleted bookmark {}", bookmark.label));
+        self.bookmarks.retain(|b| b.id != bookmark.id);
+        if self.bookmarks.is_empty() {
+            self.close_bookmarks();
+            return;
+        }
+
+        // Stay on the row that took the deleted bookmark's place.
+        let rows = self.bookmark_rows().len();
+        let row = self.bookmarks_state.selected().unwrap_or_default();
+        self.bookmarks_state.select(Some(row.min(rows - 1)));
+        if self.selected_bookmark().is_none() {
+            self.move_bookmark_selection(true);
+        }
+    }
+
+    /// Hides the bookmarks panel.
+    pub fn close_bookmarks(&mut self) {
+        self.mode = Mode::Normal;
+    }
+
+    /// Shows the key bindings.
+    pub fn open_help(&mut self) {
+        self.help_scroll = 0;
+        self.mode = Mode::Help;
+    }
+
+    pub fn close_help(&mut self) {
+        self.mode = Mode::Normal;
+    }
+
+    /// Scrolls the key bindings help by `rows`. The help is drawn no further
+    /// than its last row.
+    pub fn scroll_help(&mut self, rows: u16, down: bool) {
+        self.help_scroll = if down {
+            self.help_scroll.saturating_add(rows)
+        } else {
+            self.help_scroll.saturating_sub(rows)
+        };
+    }
+
+    /// Asks for the color, and optionally the words, to highlight or
+    /// underline the selected verses with.
+    pub fn start_annotation(&mut self, kind: AnnotationKind) {
+        if self.verse_cursor.is_none() {
+            self.status = Some(format!("Select a verse{} first", self.verse_keys_hint()));
+            return;
+        }
+
+        self.open_prompt(PromptKind::Annotate(kind));
+        if let Some(prompt) = self.prompt_mut() {
+            prompt.input = user_data::DEFAULT_COLOR.to_string();
+        }
+    }
+
+    /// Highlights or underlines the selected verses as described by `input`:
+    /// a color, such as "green" or "#ffcc00", optionally followed by the
+    /// words to mark, such as "3-7", when a single verse is selected.
+    fn annotate(&mut self, kind: AnnotationKind, input: &str) {
+        let mut color = user_data::DEFAULT_COLOR.to_string();
+        let mut words = None;
+        for part in input.split_whitespace() {
+            if part.starts_with(|c: char| c.is_ascii_digit()) {
+                match parse_word_range(part) {
+                    Some(range) => words = Some(range),
+                    None => {
+                        self.status = Some(format!("Expected words such as 3-7, found '{part}'"));
+                        return;
+                    }
+                }
+            } else if Color::from_str(part).is_ok() {
+                color = part.to_lowercase();
+            } else {
+                self.status = Some(format!("Unknown color '{part}'"));
+                return;
+            }
+        }
+
+        let Some(selected) = self.selected_verses() else {
+            return;
+        };
+        if words.is_some() && selected.start() != selected.end() {
+            self.status = Some("Words can only be marked within a single verse".to_string());
+            return;
+        }
+        let (Some(work), Some(entry)) = (self.current_work(), self.current_chapter_entry()) else {
+            return;
+        };
+        let (work_uri, chapter_id) = (work.uri.clone(), entry.id.clone());
+
+        let mut annotations = vec![];
+        for verse in &self.rendered_chapter().verses[selected] {
+            let range = match &words {
+                Some(words) => match word_offsets(&verse.text, words.clone()) {
+                    Some(range) => range,
+                    None => {
+                        self.status = Some("The verse doesn't have that many words".to_string());
+                        return;
+                    }
+                },
+                None => 0..verse.text.chars().count(),
+            };
+            annotations.push(Annotation {
+                id: 0,
+                work_uri: work_uri.clone(),
+                chapter_id: chapter_id.clone(),
+                paragraph_aid: verse.aid.clone(),
+                start_index: range.start,
+                end_index: range.end,
+                kind,
+                color: color.clone(),
+            });
+        }
+
+        for annotation in &annotations {
+            if let Some(Err(e)) = self.user_data().map(|u| u.add_annotation(annotation)) {
+                self.status = Some(format!("Unable to save annotation: {e}"));
+                break;
+            }
+        }
+        self.load_annotations();
+    }
+
+    /// Removes every highlight and underline from the selected verses.
+    pub fn remove_annotations(&mut self) {
+        let Some(selected) = self.selected_verses() else {
+            self.status = Some(format!("Select a verse{} first", self.verse_keys_hint()));
+            return;
+        };
+        let aids = self.rendered_chapter().verses[selected]
+            .iter()
+            .map(|v| v.aid.clone())
+            .collect::<Vec<_>>();
+        let ids = self
+            .annotations
+            .iter()
+            .filter(|a| aids.contains(&a.paragraph_aid))
+            .map(|a| a.id)
+            .collect::<Vec<_>>();
+        if ids.is_empty() {
+            self.status = Some("Nothing marked in the selected verses".to_string());
+            return;
+        }
+
+        for id in ids {
+            if let Some(Err(e)) = self.user_data().map(|u| u.delete_annotation(id)) {
+                self.status = Some(format!("Unable to remove annotation: {e}"));
+                break;
+            }
+        }
+        self.load_annotations();
+    }
+
+    /// Shows or hides the note panel.
+    pub fn toggle_note_panel(&mut self) {
+        self.note_panel = !self.note_panel;
+    }
+
+    /// Hides the note panel. Returns whether it was shown.
+    pub fn close_note_panel(&mut self) -> bool {
+        std::mem::take(&mut self.note_panel)
+    }
+
+    /// The note on the cursor's verse.
+    fn cursor_note(&mut self) -> Option<&Note> {
+        let aid = self.cursor_verse()?.aid.clone();
+        self.notes.iter().find(|n| n.paragraph_aid == aid)
+    }
+
+    /// Title of the note panel, naming the verse whose note is shown.
+    pub fn note_panel_title(&mut self) -> String {
+        let verse = self.cursor_verse().cloned();
+        match (verse, self.current_chapter_entry()) {
+            (Some(verse), Some(entry)) => {
+                format!("Note on {}", verse_label(&entry.title, Some(&verse)))
+            }
+            _ => "Note".to_string(),
+        }
+    }
+
+    /// Content of the note panel: the cursor verse's note with its Markdown
+    /// headings in bold, or how to write one.
+    pub fn note_panel_text(&mut self) -> Text<'static> {
+        if self.verse_cursor.is_none() {
+            return Text::raw(format!(
+                "Select a verse{} to read its note.",
+                self.verse_keys_hint()
+            ));
+        }
+        let Some(note) = self.cursor_note() else {
+            return Text::raw(match self.keymap.key_for(Action::EditNote) {
+                Some(key) => format!("No note on this verse. Press {key} to write one."),
+                None => "No note on this verse.".to_string(),
+            });
+        };
+
+        Text::from(
+            note.text
+                .lines()
+                .map(|line| {
+                    if line.starts_with('#') {
+                        let heading = line.trim_start_matches('#').trim().to_string();
+                        Line::styled(heading, Style::default().add_modifier(Modifier::BOLD))
+                    } else {
+                        Line::raw(line.to_string())
+                    }
+                })
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Edits the note on the cursor's verse, in `$VISUAL` or `$EDITOR` if
+    /// either is set and otherwise in the note panel.
+    pub fn edit_note(&mut self) {
+        let Some(verse) = self.cursor_verse().cloned() else {
+            self.status = Some(format!("Select a verse{} first", self.verse_keys_hint()));
+            return;
+        };
+        let (Some(work), Some(entry)) = (self.current_work(), self.current_chapter_entry()) else {
+            return;
+        };
+        let note = Note {
+            work_uri: work.uri.clone(),
+            chapter_id: entry.id.clone(),
+            paragraph_aid: verse.aid.clone(),
+            text: self
+                .cursor_note()
+                .map(|n| n.text.clone())
+                .unwrap_or_default(),
+        };
+
+        self.note_panel = true;
+        let editor = ["VISUAL", "EDITOR"]
+            .into_iter()
+            .filter_map(env::var_os)
+            .map(|e| e.to_string_lossy().into_owned())
+            .find(|e| !e.trim().is_empty());
+        match editor {
+            Some(editor) => {
+                self.external_edit = Some(ExternalEdit {
+                    editor,
+                    text: note.text.clone(),
+                })
+            }
+            None => self.mode = Mode::EditingNote(TextEditor::new(&note.text)),
+        }
+        self.editing_note = Some(note);
+    }
+
+    /// The note being written in the note panel.
+    pub fn note_editor_mut(&mut self) -> Option<&mut TextEditor> {
+        match &mut self.mode {
+            Mode::EditingNote(editor) => Some(editor),
+            _ => None,
+        }
+    }
+
+    /// Saves the note written in the note panel.
+    pub fn save_note_editor(&mut self) {
+        if let Mode::EditingNote(editor) = std::mem::take(&mut self.mode) {
+            self.save_note(editor.text());
+        }
+    }
+
+    /// Stops writing in the note panel without saving.
+    pub fn cancel_note_editor(&mut self) {
+        self.mode = Mode::Normal;
+        self.editing_note = None;
+    }
+
+    /// Saves the note edited in `$EDITOR`, or reports why it couldn't be.
+    pub fn finish_external_edit(&mut self, result: AppResult<String>) {
+        match result {
+            Ok(text) => self.save_note(text),
+            Err(e) => {
+                self.status = Some(format!("Unable to edit note: {e}"));
+                self.editing_note = None;
+            }
+        }
+    }
+
+    /// Stores `text` as the note being edited. Empty notes are deleted.
+    fn save_note(&mut self, text: String) {
+        let Some(mut note) = self.editing_note.take() else {
+            return;
+        };
+        let text = text.trim_end().to_string();
+        if text == note.text {
+            return;
+        }
+
+        note.text = text;
+        self.status = match self.user_data().map(|u| u.save_note(&note)) {
+            Some(Ok(())) if note.text.is_empty() => Some("Note deleted".to_string()),
+            Some(Ok(())) => Some("Note saved".to_string()),
+            Some(Err(e)) => Some(format!("Unable to save note: {e}")),
+            None => return,
+        };
+        self.load_notes();
+    }
+}
+
+/// A reference to `verse` in the chapter titled `chapter`, such as
+/// "Moses 1:39", or just the chapter when there's no verse number.
+fn verse_label(chapter: &str, verse: Option<&Verse>) -> String {
+    match verse.and_then(|v| v.number) {
+        Some(number) => format!("{chapter}:{number}"),
+        None => chapter.to_string(),
+    }
+}
+
+/// Parses a range of words such as "3-7" or "3", counting from 1.
+fn parse_word_range(s: &str) -> Option<RangeInclusive<usize>> {
+    let (first, last) = s.split_once('-').unwrap_or((s, s));
+    let range = first.parse::<usize>().ok()?..=last.parse::<usize>().ok()?;
+    (*range.start() > 0 && !range.is_empty()).then_some(range)
+}
+
+/// Character offsets in `text` spanning words `words`, counting from 1.
+fn word_offsets(text: &str, words: RangeInclusive<usize>) -> Option<Range<usize>> {
+    let mut word_ranges = vec![];
+    let mut start = None;
+    for (i, c) in text.chars().enumerate() {
+        if c.is_whitespace() {
+            if let Some(start) = start.take() {
+                word_ranges.push(start..i);
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(start) = start {
+        word_ranges.push(start..text.chars().count());
+    }
+
+    let first = word_ranges.get(words.start() - 1)?;
+    let last = word_ranges.get(words.end() - 1)?;
+    Some(first.start..last.end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_word_ranges() {
+        assert_eq!(parse_word_range("3-7"), Some(3..=7));
+        assert_eq!(parse_word_range("3"), Some(3..=3));
+        assert_eq!(parse_word_range("0-2"), None);
+        assert_eq!(parse_word_range("7-3"), None);
+        assert_eq!(parse_word_range("three"), None);
+    }
+
+    #[test]
+    fn finds_the_characters_of_words() {
+        let text = "  For behold,  this is my work ";
+        assert_eq!(word_offsets(text, 1..=1), Some(2..5));
+        assert_eq!(word_offsets(text, 2..=3), Some(6..19));
+        assert_eq!(word_offsets(text, 6..=6), Some(26..30));
+        assert_eq!(word_offsets("my work", 2..=2), Some(3..7));
+    }
+
+    #[test]
+    fn words_past_the_end_have_no_offsets() {
+        assert_eq!(word_offsets("my work", 2..=3), None);
+        assert_eq!(word_offsets("my work", 3..=3), None);
+        assert_eq!(word_offsets("", 1..=1), None);
     }
 }
@@ -0,0 +1,93 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+/// A terminal user interface for reading the scriptures.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Directory containing the scripture databases (`*.sqlite`).
+    ///
+    /// Defaults to the first of `$XDG_DATA_HOME/scripture-tui`,
+    /// `$XDG_DATA_DIRS/scripture-tui` and the current directory that holds any.
+    #[arg(
+        long,
+        value_name = "DIR",
+        env = "SCRIPTURE_TUI_DATA_DIR",
+        global = true
+    )]
+    pub data_dir: Option<PathBuf>,
+
+    /// What to do instead of opening the reader.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Print a passage, such as "John 3:16-17" or "Moses 1", and exit.
+    ///
+    /// The text is styled when printed to a terminal, unless `$NO_COLOR` is
+    /// set.
+    Show {
+        /// Book, chapter and optionally verses, such as "D&C 121:7-8, 10".
+        reference: String,
+        /// Also print the footnotes of the verses shown.
+        #[arg(short, long)]
+        footnotes: bool,
+    },
+    /// Print every verse matching a search as "Alma 32:21: text" lines.
+    ///
+    /// Matches are case-insensitive unless `--case-sensitive` is given.
+    Search {
+        /// Text to find. Its meaning depends on `--mode`.
+        query: String,
+        /// Only search this work, by title, abbreviation or URI name, such as
+        /// "BoM", "bofm" or "Book of Mormon". May be given more than once.
+        #[arg(short, long, value_name = "WORK")]
+        work: Vec<String>,
+        /// How to match the query against the text of each verse.
+        #[arg(short, long, value_enum, default_value_t = SearchMode::Phrase)]
+        mode: SearchMode,
+        /// Match upper and lower case exactly.
+        #[arg(short = 's', long)]
+        case_sensitive: bool,
+        /// Print one JSON object per matching verse instead.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Write your bookmarks, highlights, underlines and notes to a file.
+    Export {
+        /// `json` can be imported again. `markdown` is for reading, and quotes
+        /// the verses concerned.
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+        /// File to write to instead of standard output.
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Add the bookmarks, highlights, underlines and notes in a JSON export.
+    ///
+    /// Bookmarks and annotations you already have are skipped. Notes replace
+    /// any note already on the same verse.
+    Import {
+        /// File written by `export --format json`, or `-` for standard input.
+        file: PathBuf,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    Markdown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SearchMode {
+    /// The query appears anywhere, even inside a longer word.
+    Phrase,
+    /// The query appears as whole words, so "faith" doesn't match "faithful".
+    Word,
+    /// The query is a regular expression.
+    Regex,
+}
@@ -0,0 +1,53 @@
+use std::collections::BTreeMap;
+use std::{fs, io};
+
+use serde::Deserialize;
+
+use crate::app::AppResult;
+use crate::keymap::Preset;
+use crate::paths;
+
+/// File name of the configuration in the config directory.
+const CONFIG_FILE: &str = "config.toml";
+
+/// Settings read from `config.toml`.
+///
+/// ```toml
+/// keymap = "vim"
+/// auto_continue = true
+///
+/// [keys]
+/// "ctrl-n" = "next-chapter"
+/// "g b" = "bookmarks"
+/// "x" = "none"
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Key bindings to start from.
+    pub keymap: Preset,
+    /// Go on to the next chapter when scrolling past the end of one, and
+    /// back to the previous chapter when scrolling past the top.
+    pub auto_continue: bool,
+    /// Key sequences bound to action names on top of the preset's bindings.
+    /// The action "none" removes a binding.
+    pub keys: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// Reads the configuration. A missing file is the same as an empty one,
+    /// but a file that can't be read or parsed is an error.
+    pub fn load() -> AppResult<Self> {
+        let Some(dir) = paths::config_dir() else {
+            return Ok(Self::default());
+        };
+
+        let path = dir.join(CONFIG_FILE);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("{}: {e}", path.display()).into()),
+        };
+        toml::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e.message()).into())
+    }
+}
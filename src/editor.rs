@@ -0,0 +1,132 @@
+/// A small multiline text editor, for writing notes without leaving the
+/// application.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEditor {
+    lines: Vec<String>,
+    /// Line the cursor is on.
+    row: usize,
+    /// Character the cursor is before within its line.
+    column: usize,
+}
+
+impl TextEditor {
+    /// Starts editing `text` with the cursor at its end.
+    pub fn new(text: &str) -> Self {
+        let mut lines = text.lines().map(String::from).collect::<Vec<_>>();
+        if lines.is_empty() {
+            lines.push(String::new());
+        }
+        let row = lines.len() - 1;
+        let column = lines[row].chars().count();
+        Self { lines, row, column }
+    }
+
+    /// The text being edited.
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// Line and character the cursor is at.
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.row, self.column)
+    }
+
+    /// Byte index of the cursor within its line.
+    fn byte_index(&self) -> usize {
+        let line = &self.lines[self.row];
+        line.char_indices()
+            .nth(self.column)
+            .map(|(i, _)| i)
+            .unwrap_or(line.len())
+    }
+
+    fn line_len(&self, row: usize) -> usize {
+        self.lines[row].chars().count()
+    }
+
+    pub fn insert(&mut self, c: char) {
+        let i = self.byte_index();
+        self.lines[self.row].insert(i, c);
+        self.column += 1;
+    }
+
+    /// Splits the line at the cursor.
+    pub fn newline(&mut self) {
+        let i = self.byte_index();
+        let rest = self.lines[self.row].split_off(i);
+        self.row += 1;
+        self.column = 0;
+        self.lines.insert(self.row, rest);
+    }
+
+    /// Deletes the character before the cursor, joining lines at the start
+    /// of one.
+    pub fn backspace(&mut self) {
+        if self.column > 0 {
+            self.column -= 1;
+            let i = self.byte_index();
+            self.lines[self.row].remove(i);
+        } else if self.row > 0 {
+            let line = self.lines.remove(self.row);
+            self.row -= 1;
+            self.column = self.line_len(self.row);
+            self.lines[self.row].push_str(&line);
+        }
+    }
+
+    /// Deletes the character after the cursor, joining lines at the end of
+    /// one.
+    pub fn delete(&mut self) {
+        if self.column < self.line_len(self.row) {
+            let i = self.byte_index();
+            self.lines[self.row].remove(i);
+        } else if self.row + 1 < self.lines.len() {
+            let line = self.lines.remove(self.row + 1);
+            self.lines[self.row].push_str(&line);
+        }
+    }
+
+    pub fn left(&mut self) {
+        if self.column > 0 {
+            self.column -= 1;
+        } else if self.row > 0 {
+            self.row -= 1;
+            self.column = self.line_len(self.row);
+        }
+    }
+
+    pub fn right(&mut self) {
+        if self.column < self.line_len(self.row) {
+            self.column += 1;
+        } else if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.column = 0;
+        }
+    }
+
+    pub fn up(&mut self) {
+        if self.row > 0 {
+            self.row -= 1;
+            self.column = self.column.min(self.line_len(self.row));
+        }
+    }
+
+    pub fn down(&mut self) {
+        if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.column = self.column.min(self.line_len(self.row));
+        }
+    }
+
+    pub fn home(&mut self) {
+        self.column = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.column = self.line_len(self.row);
+    }
+}
@@ -1,6 +1,10 @@
 use crate::app::AppResult;
 use crossterm::event::{self, Event as CrosstermEvent, KeyEvent, MouseEvent};
-use std::sync::mpsc;
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+    mpsc,
+};
 use std::thread;
 use std::time::{Duration, Instant};
 
@@ -27,6 +31,10 @@ pub struct EventHandler {
     receiver: mpsc::Receiver<Event>,
     /// Event handler thread.
     handler: thread::JoinHandle<()>,
+    /// Set to stop reading terminal events.
+    paused: Arc<AtomicBool>,
+    /// Set by the handler thread once it has stopped reading.
+    idle: Arc<AtomicBool>,
 }
 
 impl EventHandler {
@@ -34,27 +42,49 @@ impl EventHandler {
     pub fn new(tick_rate: u64) -> Self {
         let tick_rate = Duration::from_millis(tick_rate);
         let (sender, receiver) = mpsc::channel();
+        let paused = Arc::new(AtomicBool::new(false));
+        let idle = Arc::new(AtomicBool::new(false));
         let handler = {
             let sender = sender.clone();
+            let paused = paused.clone();
+            let idle = idle.clone();
             thread::spawn(move || {
                 let mut last_tick = Instant::now();
                 loop {
+                    if paused.load(Ordering::SeqCst) {
+                        idle.store(true, Ordering::SeqCst);
+                        thread::sleep(Duration::from_millis(10));
+                        continue;
+                    }
+                    idle.store(false, Ordering::SeqCst);
+
                     let timeout = tick_rate
                         .checked_sub(last_tick.elapsed())
                         .unwrap_or(tick_rate);
 
-                    if event::poll(timeout).expect("no events available") {
-                        match event::read().expect("unable to read event") {
-                            CrosstermEvent::Key(e) => sender.send(Event::Key(e)),
-                            CrosstermEvent::Mouse(e) => sender.send(Event::Mouse(e)),
-                            CrosstermEvent::Resize(w, h) => sender.send(Event::Resize(w, h)),
-                            _ => unimplemented!(),
+                    // Stop on errors. `next` reports that the thread stopped.
+                    match event::poll(timeout) {
+                        Ok(true) => {
+                            let event = match event::read() {
+                                Ok(CrosstermEvent::Key(e)) => Event::Key(e),
+                                Ok(CrosstermEvent::Mouse(e)) => Event::Mouse(e),
+                                Ok(CrosstermEvent::Resize(w, h)) => Event::Resize(w, h),
+                                // Focus changes and pasted text aren't used.
+                                Ok(_) => continue,
+                                Err(_) => break,
+                            };
+                            if sender.send(event).is_err() {
+                                break;
+                            }
                         }
-                        .expect("failed to send terminal event")
+                        Ok(false) => {}
+                        Err(_) => break,
                     }
 
                     if last_tick.elapsed() >= tick_rate {
-                        sender.send(Event::Tick).expect("failed to send tick event");
+                        if sender.send(Event::Tick).is_err() {
+                            break;
+                        }
                         last_tick = Instant::now();
                     }
                 }
@@ -64,14 +94,38 @@ impl EventHandler {
             sender,
             receiver,
             handler,
+            paused,
+            idle,
         }
     }
 
+    /// Stops reading terminal events, so another program can read them.
+    /// Returns once the handler thread has stopped reading, or straight away
+    /// if it has exited after an error.
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+        while !self.idle.load(Ordering::SeqCst) && !self.handler.is_finished() {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Starts reading terminal events again after [`EventHandler::pause`].
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
     /// Receive the next event from the handler thread.
     ///
     /// This function will always block the current thread if
     /// there is no data available and it's possible for more data to be sent.
+    /// It returns an error if the handler thread has stopped.
     pub fn next(&self) -> AppResult<Event> {
-        Ok(self.receiver.recv()?)
+        loop {
+            match self.receiver.recv_timeout(Duration::from_secs(1)) {
+                Ok(event) => return Ok(event),
+                Err(mpsc::RecvTimeoutError::Timeout) if !self.handler.is_finished() => {}
+                Err(_) => return Err("Stopped reading terminal events".into()),
+            }
+        }
     }
 }
@@ -0,0 +1,465 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::AppResult;
+use crate::cli::ExportFormat;
+use crate::scriptures::{Scriptures, Verse};
+use crate::user_data::{Annotation, AnnotationKind, Bookmark, Note, UserData};
+
+/// Version of the JSON format written by [`UserExport::to_json`].
+const FORMAT_VERSION: u32 = 1;
+
+/// Everything in the user database, as written by `scripture-tui export` and
+/// read by `scripture-tui import`.
+///
+/// The JSON looks like this. Chapters are named by `subitem.id` and verses by
+/// `data-aid`, as in the scripture databases. `verse_aid` is `null` for a
+/// bookmark of a whole chapter. Annotation offsets count characters of the
+/// verse's text, leaving out the verse number and footnote markers, and
+/// `end_index` is just past the last character marked. `kind` is `highlight`
+/// or `underline`. Notes are Markdown.
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "bookmarks": [
+///     { "list": "Sunday lesson", "work_uri": "/scriptures/pgp", "chapter_id": "128450077",
+///       "verse_aid": "128450108", "label": "Moses 1:39" }
+///   ],
+///   "annotations": [
+///     { "work_uri": "/scriptures/pgp", "chapter_id": "128450077", "paragraph_aid": "128450108",
+///       "start_index": 0, "end_index": 34, "kind": "highlight", "color": "yellow" }
+///   ],
+///   "notes": [
+///     { "work_uri": "/scriptures/pgp", "chapter_id": "128450077", "paragraph_aid": "128450108",
+///       "text": "# Purpose\nImmortality *and* eternal life." }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserExport {
+    pub version: u32,
+    #[serde(default)]
+    pub bookmarks: Vec<Bookmark>,
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
+    #[serde(default)]
+    pub notes: Vec<Note>,
+}
+
+/// How many of each kind of item an import added.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ImportCounts {
+    pub bookmarks: usize,
+    pub annotations: usize,
+    pub notes: usize,
+}
+
+impl UserExport {
+    /// Reads everything in the user database.
+    pub fn read(user_data: &UserData) -> AppResult<Self> {
+        Ok(UserExport {
+            version: FORMAT_VERSION,
+            bookmarks: user_data.bookmarks()?,
+            annotations: user_data.all_annotations()?,
+            notes: user_data.all_notes()?,
+        })
+    }
+
+    pub fn to_json(&self) -> AppResult<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> AppResult<Self> {
+        let export: UserExport = serde_json::from_str(json)?;
+        if export.version > FORMAT_VERSION {
+            return Err(format!(
+                "Export format version {} is newer than this version of scripture-tui understands",
+                export.version
+            )
+            .into());
+        }
+        Ok(export)
+    }
+
+    /// Adds everything to the user database, or nothing if any of it can't be.
+    /// Anything that's already there is skipped, as are empty notes, and
+    /// other notes replace the verse's existing note.
+    pub fn import(&self, user_data: &UserData) -> AppResult<ImportCounts> {
+        user_data.in_transaction(|user_data| {
+            let mut counts = ImportCounts::default();
+
+            let existing = user_data.bookmarks()?;
+            for bookmark in &self.bookmarks {
+                let duplicate = existing.iter().any(|b| {
+                    b.list == bookmark.list
+                        && b.work_uri == bookmark.work_uri
+                        && b.chapter_id == bookmark.chapter_id
+                        && b.verse_aid == bookmark.verse_aid
+                });
+                if !duplicate {
+                    user_data.add_bookmark(bookmark)?;
+                    counts.bookmarks += 1;
+                }
+            }
+
+            let existing = user_data.all_annotations()?;
+            for annotation in &self.annotations {
+                let duplicate = existing
+                    .iter()
+                    .any(|a| Annotation { id: 0, ..a.clone() } == *annotation);
+                if !duplicate {
+                    user_data.add_annotation(annotation)?;
+                    counts.annotations += 1;
+                }
+            }
+
+            // Saving an empty note deletes it, which would lose the reader's
+            // own note on the verse.
+            let existing = user_data.all_notes()?;
+            for note in self
+                .notes
+                .iter()
+                .filter(|n| !n.text.trim().is_empty() && !existing.contains(n))
+            {
+                user_data.save_note(note)?;
+                counts.notes += 1;
+            }
+            Ok(counts)
+        })
+    }
+
+    /// A readable Markdown document of every chapter with bookmarks,
+    /// annotations or notes, quoting the verses concerned.
+    pub fn to_markdown(&self, scriptures: &mut Scriptures) -> AppResult<String> {
+        // Everything grouped by chapter, with chapters in reading order and
+        // those no longer installed at the end.
+        let mut chapters: BTreeMap<_, ChapterItems> = BTreeMap::new();
+        let key = |work_uri, chapter_id| {
+            let location = scriptures.locate(work_uri, chapter_id);
+            (location.is_none(), location, work_uri, chapter_id)
+        };
+        for bookmark in &self.bookmarks {
+            let key = key(&bookmark.work_uri, &bookmark.chapter_id);
+            chapters.entry(key).or_default().bookmarks.push(bookmark);
+        }
+        for annotation in &self.annotations {
+            let key = key(&annotation.work_uri, &annotation.chapter_id);
+            chapters
+                .entry(key)
+                .or_default()
+                .annotations
+                .push(annotation);
+        }
+        for note in &self.notes {
+            let key = key(&note.work_uri, &note.chapter_id);
+            chapters.entry(key).or_default().notes.push(note);
+        }
+
+        let mut markdown = String::from("# Bookmarks, annotations and notes\n");
+        for ((_, location, work_uri, chapter_id), items) in chapters {
+            let Some(location) = location else {
+                writeln!(
+                    markdown,
+                    "\n## {work_uri} chapter {chapter_id} (not installed)\n"
+                )?;
+                items.write_unresolved(&mut markdown)?;
+                continue;
+            };
+
+            let title = scriptures.works[location.work].books[location.book].chapters
+                [location.chapter]
+                .title
+                .clone();
+            let annotations = items
+                .annotations
+                .iter()
+                .map(|&a| a.clone())
+                .collect::<Vec<_>>();
+            let verses = match scriptures.chapter(location.work, location.book, location.chapter)? {
+                Some(chapter) => chapter.text(&annotations).verses,
+                None => vec![],
+            };
+
+            writeln!(markdown, "\n## {title}")?;
+            items.write(&mut markdown, &title, &verses)?;
+        }
+        Ok(markdown)
+    }
+}
+
+/// Runs `scripture-tui export`, writing to `output` or standard output.
+pub fn export(data_dir: &Path, format: ExportFormat, output: Option<&Path>) -> AppResult<()> {
+    let export = UserExport::read(&UserData::open()?)?;
+    let contents = match format {
+        ExportFormat::Json => export.to_json()?,
+        ExportFormat::Markdown => export.to_markdown(&mut Scriptures::load(data_dir))?,
+    };
+
+    match output {
+        Some(path) => fs::write(path, contents + "\n")?,
+        None => println!("{contents}"),
+    }
+    Ok(())
+}
+
+/// Runs `scripture-tui import`, reading `file` or standard input for `-`.
+pub fn import(file: &Path) -> AppResult<()> {
+    let json = if file == Path::new("-") {
+        let mut json = String::new();
+        io::stdin().read_to_string(&mut json)?;
+        json
+    } else {
+        fs::read_to_string(file)?
+    };
+
+    let counts = UserExport::from_json(&json)?.import(&UserData::open()?)?;
+    println!(
+        "Imported {} bookmarks, {} annotations and {} notes",
+        counts.bookmarks, counts.annotations, counts.notes
+    );
+    Ok(())
+}
+
+/// What the reader has in one chapter.
+#[derive(Debug, Default)]
+struct ChapterItems<'a> {
+    bookmarks: Vec<&'a Bookmark>,
+    annotations: Vec<&'a Annotation>,
+    notes: Vec<&'a Note>,
+}
+
+impl ChapterItems<'_> {
+    /// Writes the chapter's bookmarks, then each verse with annotations or a
+    /// note, quoting the verse.
+    fn write(&self, markdown: &mut String, title: &str, verses: &[Verse]) -> AppResult<()> {
+        if !self.bookmarks.is_empty() {
+            writeln!(markdown)?;
+            for bookmark in &self.bookmarks {
+                writeln!(
+                    markdown,
+                    "- Bookmarked {} in *{}*",
+                    bookmark.label, bookmark.list
+                )?;
+            }
+        }
+
+        for verse in verses {
+            let annotations = self
+                .annotations
+                .iter()
+                .filter(|a| a.paragraph_aid == verse.aid)
+                .collect::<Vec<_>>();
+            let note = self.notes.iter().find(|n| n.paragraph_aid == verse.aid);
+            if annotations.is_empty() && note.is_none() {
+                continue;
+            }
+
+            let reference = match verse.number {
+                Some(number) => format!("{title}:{number}"),
+                None => title.to_string(),
+            };
+            writeln!(markdown, "\n### {reference}\n\n> {}", verse.text.trim())?;
+            if !annotations.is_empty() {
+                writeln!(markdown)?;
+            }
+            for annotation in annotations {
+                let marked = verse
+                    .text
+                    .chars()
+                    .skip(annotation.start_index)
+                    .take(annotation.end_index.saturating_sub(annotation.start_index))
+                    .collect::<String>();
+                writeln!(
+                    markdown,
+                    "- {} ({}): \"{}\"",
+                    kind_name(annotation.kind),
+                    annotation.color,
+                    marked.trim()
+                )?;
+            }
+            if let Some(note) = note {
+                writeln!(markdown, "\n{}", note.text)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes what's known about a chapter that isn't installed.
+    fn write_unresolved(&self, markdown: &mut String) -> AppResult<()> {
+        for bookmark in &self.bookmarks {
+            writeln!(
+                markdown,
+                "- Bookmarked {} in *{}*",
+                bookmark.label, bookmark.list
+            )?;
+        }
+        for annotation in &self.annotations {
+            writeln!(
+                markdown,
+                "- {} ({}) in paragraph {}",
+                kind_name(annotation.kind),
+                annotation.color,
+                annotation.paragraph_aid
+            )?;
+        }
+        for note in &self.notes {
+            writeln!(
+                markdown,
+                "\nNote on paragraph {}:\n\n{}",
+                note.paragraph_aid, note.text
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn kind_name(kind: AnnotationKind) -> &'static str {
+    match kind {
+        AnnotationKind::Highlight => "Highlight",
+        AnnotationKind::Underline => "Underline",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark(list: &str, verse_aid: &str) -> Bookmark {
+        Bookmark {
+            id: 0,
+            list: list.into(),
+            work_uri: "/scriptures/pgp".into(),
+            chapter_id: "128450077".into(),
+            verse_aid: Some(verse_aid.into()),
+            label: "Moses 1:39".into(),
+        }
+    }
+
+    fn annotation(start_index: usize, end_index: usize) -> Annotation {
+        Annotation {
+            id: 0,
+            work_uri: "/scriptures/pgp".into(),
+            chapter_id: "128450077".into(),
+            paragraph_aid: "128450108".into(),
+            start_index,
+            end_index,
+            kind: AnnotationKind::Underline,
+            color: "#ffcc00".into(),
+        }
+    }
+
+    fn note(paragraph_aid: &str, text: &str) -> Note {
+        Note {
+            work_uri: "/scriptures/pgp".into(),
+            chapter_id: "128450077".into(),
+            paragraph_aid: paragraph_aid.into(),
+            text: text.into(),
+        }
+    }
+
+    fn export() -> UserExport {
+        UserExport {
+            version: FORMAT_VERSION,
+            bookmarks: vec![bookmark("Sunday lesson", "128450108")],
+            annotations: vec![annotation(0, 34)],
+            notes: vec![note(
+                "128450108",
+                "# Purpose\nImmortality *and* eternal life.",
+            )],
+        }
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let export = export();
+        assert_eq!(
+            UserExport::from_json(&export.to_json().unwrap()).unwrap(),
+            export
+        );
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        let json = format!(r#"{{ "version": {} }}"#, FORMAT_VERSION + 1);
+        let error = UserExport::from_json(&json).unwrap_err().to_string();
+        assert!(error.contains("newer"), "{error}");
+
+        let empty = UserExport::from_json(r#"{ "version": 1 }"#).unwrap();
+        assert_eq!(
+            empty,
+            UserExport {
+                version: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn imports_everything_into_an_empty_database() {
+        let user_data = UserData::open_in_memory().unwrap();
+        let counts = export().import(&user_data).unwrap();
+
+        assert_eq!(
+            counts,
+            ImportCounts {
+                bookmarks: 1,
+                annotations: 1,
+                notes: 1
+            }
+        );
+        // Ids are left out of the JSON.
+        let read = UserExport::read(&user_data).unwrap();
+        assert_eq!(read.to_json().unwrap(), export().to_json().unwrap());
+    }
+
+    #[test]
+    fn importing_again_skips_what_is_already_there() {
+        let user_data = UserData::open_in_memory().unwrap();
+        export().import(&user_data).unwrap();
+
+        let mut again = export();
+        again.bookmarks.push(bookmark("Another list", "128450108"));
+        again.annotations.push(annotation(5, 10));
+        let counts = again.import(&user_data).unwrap();
+
+        assert_eq!(
+            counts,
+            ImportCounts {
+                bookmarks: 1,
+                annotations: 1,
+                notes: 0
+            }
+        );
+        assert_eq!(UserExport::read(&user_data).unwrap().bookmarks.len(), 2);
+        assert_eq!(user_data.all_annotations().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn empty_notes_dont_delete_existing_ones() {
+        let user_data = UserData::open_in_memory().unwrap();
+        user_data.save_note(&note("128450108", "Mine")).unwrap();
+
+        let import = UserExport {
+            version: FORMAT_VERSION,
+            notes: vec![note("128450108", "  "), note("128450109", "Theirs")],
+            ..Default::default()
+        };
+        let counts = import.import(&user_data).unwrap();
+
+        assert_eq!(counts.notes, 1);
+        let mut notes = user_data.all_notes().unwrap();
+        notes.sort_by(|a, b| a.paragraph_aid.cmp(&b.paragraph_aid));
+        assert_eq!(
+            notes,
+            [note("128450108", "Mine"), note("128450109", "Theirs")]
+        );
+    }
+}
@@ -1,101 +1,270 @@
-use crate::app::{App, AppResult};
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind};
-use tui::widgets::{Paragraph, Wrap};
+use crate::app::{App, AppResult, Mode, PromptKind, Scroll};
+use crate::keymap::Action;
+use crate::user_data::AnnotationKind;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 
 /// Handles the key events and updates the state of [`App`].
 pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
+    match app.mode {
+        Mode::Prompt(_) => return handle_prompt_key_events(key_event, app),
+        Mode::SearchResults => return handle_search_results_key_events(key_event, app),
+        Mode::Bookmarks => return handle_bookmarks_key_events(key_event, app),
+        Mode::EditingNote(_) => return handle_note_editor_key_events(key_event, app),
+        Mode::Help => return handle_help_key_events(key_event, app),
+        Mode::Normal => {}
+    }
+
+    // Exit application on `Ctrl-C`, whatever the key bindings are
+    if let KeyCode::Char('c') | KeyCode::Char('C') = key_event.code
+        && key_event.modifiers == KeyModifiers::CONTROL
+    {
+        app.quit();
+        return Ok(());
+    }
+
+    if let Some((action, count)) = app.keymap.press(key_event) {
+        perform(action, count, app);
+    }
+    Ok(())
+}
+
+/// Does what a key binding asks, `count` times where that makes sense.
+fn perform(action: Action, count: Option<usize>, app: &mut App) {
+    // `5gg` and `5G` go to verse 5, the way they go to a line in vim.
+    if let (Action::Top | Action::Bottom, Some(number)) = (action, count) {
+        app.scroll_to_verse(number as u32);
+        return;
+    }
+
+    let times = if action.repeats() {
+        count.unwrap_or(1)
+    } else {
+        1
+    };
+    for _ in 0..times {
+        match action {
+            Action::Quit => app.quit(),
+            // Deselect the footnote, collapse the verse selection or close
+            // the note panel, and otherwise exit the application on cancel
+            Action::Cancel => {
+                if !app.clear() {
+                    app.quit();
+                }
+            }
+            Action::Clear => {
+                app.clear();
+            }
+            Action::Help => app.open_help(),
+            Action::Up => app.arrow_up(),
+            Action::Down => app.arrow_down(),
+            Action::FocusLeft => app.arrow_left(),
+            Action::FocusRight => app.arrow_right(),
+            Action::NextWork => app.update_works(true),
+            Action::PrevWork => app.update_works(false),
+            Action::NextBook => app.update_books(true),
+            Action::PrevBook => app.update_books(false),
+            Action::NextChapter => {
+                app.next_chapter(true);
+            }
+            Action::PrevChapter => {
+                app.next_chapter(false);
+            }
+            // Scroll the focused pane, or the chapter text while a list is
+            // focused
+            Action::PageDown => app.scroll_pane(app.focused_pane(), Scroll::Page, true),
+            Action::PageUp => app.scroll_pane(app.focused_pane(), Scroll::Page, false),
+            Action::HalfPageDown => app.scroll_pane(app.focused_pane(), Scroll::HalfPage, true),
+            Action::HalfPageUp => app.scroll_pane(app.focused_pane(), Scroll::HalfPage, false),
+            Action::LineDown => app.scroll_pane(app.focused_pane(), Scroll::Line, true),
+            Action::LineUp => app.scroll_pane(app.focused_pane(), Scroll::Line, false),
+            Action::Bottom => app.scroll_pane(app.focused_pane(), Scroll::End, true),
+            Action::Top => app.scroll_pane(app.focused_pane(), Scroll::End, false),
+            Action::NextVerse => app.move_verse_cursor(true, false),
+            Action::PrevVerse => app.move_verse_cursor(false, false),
+            Action::ExtendNextVerse => app.move_verse_cursor(true, true),
+            Action::ExtendPrevVerse => app.move_verse_cursor(false, true),
+            Action::NextFootnote => app.move_footnote_selection(true),
+            Action::PrevFootnote => app.move_footnote_selection(false),
+            Action::NextLink => app.move_link_selection(true),
+            Action::PrevLink => app.move_link_selection(false),
+            Action::FollowLink => app.follow_link(),
+            Action::Back => app.go_back(),
+            Action::Forward => app.go_forward(),
+            Action::Bookmark => app.start_bookmark(),
+            Action::Bookmarks => app.open_bookmarks(),
+            Action::Highlight => app.start_annotation(AnnotationKind::Highlight),
+            Action::Underline => app.start_annotation(AnnotationKind::Underline),
+            Action::RemoveAnnotations => app.remove_annotations(),
+            Action::ToggleNotes => app.toggle_note_panel(),
+            Action::EditNote => app.edit_note(),
+            Action::Goto => app.open_prompt(PromptKind::Goto),
+            Action::Search => app.open_prompt(PromptKind::Search),
+            Action::ReaderMode => app.toggle_reader_mode(),
+        }
+    }
+}
+
+/// Handles the key events while reading the key bindings.
+fn handle_help_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
+    match key_event.code {
+        KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') | KeyCode::Char('?') => app.close_help(),
+        KeyCode::Char('c') | KeyCode::Char('C') if key_event.modifiers == KeyModifiers::CONTROL => {
+            app.quit()
+        }
+        KeyCode::Up | KeyCode::Char('k') => app.scroll_help(1, false),
+        KeyCode::Down | KeyCode::Char('j') => app.scroll_help(1, true),
+        KeyCode::PageUp => app.scroll_help(app.text_rect.height, false),
+        KeyCode::PageDown | KeyCode::Char(' ') => app.scroll_help(app.text_rect.height, true),
+        KeyCode::Home => app.scroll_help(u16::MAX, false),
+        KeyCode::End => app.scroll_help(u16::MAX, true),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Handles the key events while typing into the prompt.
+fn handle_prompt_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
     match key_event.code {
-        // Exit application on `ESC` or `q`
-        KeyCode::Esc | KeyCode::Char('q') => {
-            app.quit();
+        KeyCode::Esc => app.cancel_prompt(),
+        KeyCode::Char('c') | KeyCode::Char('C') if key_event.modifiers == KeyModifiers::CONTROL => {
+            app.cancel_prompt()
         }
-        // Exit application on `Ctrl-C`
-        KeyCode::Char('c') | KeyCode::Char('C') => {
-            if key_event.modifiers == KeyModifiers::CONTROL {
-                app.quit();
+        KeyCode::Enter => app.submit_prompt(),
+        KeyCode::Backspace => {
+            if let Some(prompt) = app.prompt_mut() {
+                if prompt.input.is_empty() {
+                    app.cancel_prompt();
+                } else {
+                    prompt.input.pop();
+                }
             }
         }
-        KeyCode::Up => {
-            app.arrow_up();
+        // Clear the input on `Ctrl-U`
+        KeyCode::Char('u') if key_event.modifiers == KeyModifiers::CONTROL => {
+            if let Some(prompt) = app.prompt_mut() {
+                prompt.input.clear();
+            }
         }
-        KeyCode::Down => {
-            app.arrow_down();
+        KeyCode::Char(c) if !key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+            if let Some(prompt) = app.prompt_mut() {
+                prompt.input.push(c);
+            }
         }
-        KeyCode::Left => {
-            app.arrow_left();
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Handles the key events while choosing a search result.
+fn handle_search_results_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
+    match key_event.code {
+        KeyCode::Esc | KeyCode::Char('q') => app.close_search_results(),
+        KeyCode::Char('c') | KeyCode::Char('C') if key_event.modifiers == KeyModifiers::CONTROL => {
+            app.quit()
         }
-        KeyCode::Right => {
-            app.arrow_right();
+        KeyCode::Up => app.move_search_selection(false),
+        KeyCode::Down => app.move_search_selection(true),
+        KeyCode::Enter => app.open_search_result(),
+        KeyCode::Char('/') => app.open_prompt(PromptKind::Search),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Handles the key events while choosing a bookmark.
+fn handle_bookmarks_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
+    match key_event.code {
+        KeyCode::Esc | KeyCode::Char('q') => app.close_bookmarks(),
+        KeyCode::Char('c') | KeyCode::Char('C') if key_event.modifiers == KeyModifiers::CONTROL => {
+            app.quit()
         }
-        // Other handlers you could add here.
+        KeyCode::Up | KeyCode::Char('k') => app.move_bookmark_selection(false),
+        KeyCode::Down | KeyCode::Char('j') => app.move_bookmark_selection(true),
+        KeyCode::Enter => app.open_bookmark(),
+        KeyCode::Char('d') | KeyCode::Delete => app.delete_bookmark(),
         _ => {}
     }
     Ok(())
 }
 
+/// Handles the key events while writing a note.
+fn handle_note_editor_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
+    let control = key_event.modifiers.contains(KeyModifiers::CONTROL);
+    match key_event.code {
+        KeyCode::Esc => app.cancel_note_editor(),
+        KeyCode::Char('s') | KeyCode::Char('S') if control => app.save_note_editor(),
+        code => {
+            let Some(editor) = app.note_editor_mut() else {
+                return Ok(());
+            };
+            match code {
+                KeyCode::Enter => editor.newline(),
+                KeyCode::Backspace => editor.backspace(),
+                KeyCode::Delete => editor.delete(),
+                KeyCode::Left => editor.left(),
+                KeyCode::Right => editor.right(),
+                KeyCode::Up => editor.up(),
+                KeyCode::Down => editor.down(),
+                KeyCode::Home => editor.home(),
+                KeyCode::End => editor.end(),
+                KeyCode::Char(c) if !control => editor.insert(c),
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Handles the mouse events and updates the state of [`App`].
 pub fn handle_mouse_events(mouse_event: MouseEvent, app: &mut App) -> AppResult<()> {
     match mouse_event.kind {
-        // MouseEventKind::Down(_) => todo!(),
-        // MouseEventKind::Up(_) => todo!(),
-        // MouseEventKind::Drag(_) => todo!(),
-        // MouseEventKind::Moved => todo!(),
-        MouseEventKind::ScrollDown => {
-            if mouse_event.column <= app.text_rect.right()
-                && mouse_event.column >= app.text_rect.left()
-                && mouse_event.row >= app.text_rect.top()
-                && mouse_event.row <= app.text_rect.bottom()
-            {
-                let paragraph = Paragraph::new(app.chapter_text()).wrap(Wrap { trim: false });
-                let line_count = paragraph.line_count(app.text_rect.width) as u16;
-
-                let max_scroll = if line_count < app.text_rect.height {
-                    0
-                } else {
-                    line_count - app.text_rect.height
-                };
-                app.text_scroll = u16::min(max_scroll, app.text_scroll + 1)
-            } else if mouse_event.column <= app.footnote_rect.right()
-                && mouse_event.column >= app.footnote_rect.left()
-                && mouse_event.row >= app.footnote_rect.top()
-                && mouse_event.row <= app.footnote_rect.bottom()
-            {
-                let paragraph =
-                    Paragraph::new(app.chapter_footnotes_text()).wrap(Wrap { trim: false });
-                let line_count = paragraph.line_count(app.footnote_rect.width) as u16;
-
-                let max_scroll = if line_count < app.footnote_rect.height {
-                    0
-                } else {
-                    line_count - app.footnote_rect.height
-                };
-                app.footnote_scroll = u16::min(max_scroll, app.footnote_scroll + 1)
-            }
+        // Select list rows, verses and footnote markers by clicking, and
+        // verse ranges by dragging
+        MouseEventKind::Down(MouseButton::Left) if app.mode == Mode::Normal => {
+            app.click(mouse_event.column, mouse_event.row);
         }
-        MouseEventKind::ScrollUp => {
-            if mouse_event.column <= app.text_rect.right()
-                && mouse_event.column >= app.text_rect.left()
-                && mouse_event.row >= app.text_rect.top()
-                && mouse_event.row <= app.text_rect.bottom()
-            {
-                app.text_scroll = if app.text_scroll == 0 {
-                    0
-                } else {
-                    app.text_scroll - 1
-                }
-            } else if mouse_event.column <= app.footnote_rect.right()
-                && mouse_event.column >= app.footnote_rect.left()
-                && mouse_event.row >= app.footnote_rect.top()
-                && mouse_event.row <= app.footnote_rect.bottom()
-            {
-                app.footnote_scroll = if app.footnote_scroll == 0 {
-                    0
-                } else {
-                    app.footnote_scroll - 1
-                }
+        MouseEventKind::Drag(MouseButton::Left) => {
+            app.drag(mouse_event.column, mouse_event.row);
+        }
+        MouseEventKind::Up(MouseButton::Left) => {
+            app.end_drag();
+        }
+        MouseEventKind::ScrollDown | MouseEventKind::ScrollUp => {
+            let down = mouse_event.kind == MouseEventKind::ScrollDown;
+            if let Some(pane) = app.pane_at(mouse_event.column, mouse_event.row) {
+                app.scroll_pane(pane, Scroll::Line, down);
             }
         }
         _ => {}
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scriptures::Scriptures;
+
+    #[test]
+    fn counts_before_top_and_bottom_go_to_a_verse() {
+        let mut app = App::new(Scriptures::default());
+        app.status = None;
+
+        perform(Action::Bottom, Some(5), &mut app);
+        assert_eq!(app.status.as_deref(), Some("No verse 5 in this chapter"));
+    }
+
+    #[test]
+    fn cancel_quits_once_there_is_nothing_to_clear() {
+        let mut app = App::new(Scriptures::default());
+        app.note_panel = true;
+
+        perform(Action::Clear, None, &mut app);
+        perform(Action::Clear, None, &mut app);
+        assert!(!app.note_panel);
+        assert!(app.running);
+
+        perform(Action::Cancel, None, &mut app);
+        assert!(!app.running);
+    }
+}
@@ -0,0 +1,44 @@
+/// Most places remembered. The oldest are forgotten first.
+const MAX_LEN: usize = 200;
+
+/// Places visited, for going back and forward the way a web browser does.
+#[derive(Debug, Clone)]
+pub struct History<T> {
+    back: Vec<T>,
+    forward: Vec<T>,
+}
+
+impl<T> Default for History<T> {
+    fn default() -> Self {
+        Self {
+            back: vec![],
+            forward: vec![],
+        }
+    }
+}
+
+impl<T> History<T> {
+    /// Records `current` as the place being left for somewhere new. Anything
+    /// that could be gone forward to is forgotten.
+    pub fn push(&mut self, current: T) {
+        if self.back.len() == MAX_LEN {
+            self.back.remove(0);
+        }
+        self.back.push(current);
+        self.forward.clear();
+    }
+
+    /// The place before `current`, if any.
+    pub fn back(&mut self, current: T) -> Option<T> {
+        let previous = self.back.pop()?;
+        self.forward.push(current);
+        Some(previous)
+    }
+
+    /// The place `current` was gone back from, if any.
+    pub fn forward(&mut self, current: T) -> Option<T> {
+        let next = self.forward.pop()?;
+        self.back.push(current);
+        Some(next)
+    }
+}
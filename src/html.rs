@@ -0,0 +1,1109 @@
+use std::borrow::Cow;
+
+use roxmltree::{Document, Node, ParsingOptions};
+use tui::{
+    layout::Alignment,
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+};
+
+use crate::scriptures::{ChapterText, Marker, Verse};
+use crate::user_data::Annotation;
+
+/// Elements whose content flows within a line, rather than starting a block
+/// of its own.
+const INLINE_ELEMENTS: &[&str] = &[
+    "a", "abbr", "b", "br", "cite", "em", "i", "small", "span", "strong", "sub", "sup", "u",
+];
+
+/// Elements that never have content, which HTML lets go unclosed.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// HTML named character references and the characters they stand for. The
+/// five XML defines are left alone.
+const ENTITIES: &[(&str, &str)] = &[
+    ("nbsp", "\u{a0}"),
+    ("ensp", "\u{2002}"),
+    ("emsp", "\u{2003}"),
+    ("thinsp", "\u{2009}"),
+    ("shy", "\u{ad}"),
+    ("ndash", "–"),
+    ("mdash", "—"),
+    ("hellip", "…"),
+    ("lsquo", "‘"),
+    ("rsquo", "’"),
+    ("sbquo", "‚"),
+    ("ldquo", "“"),
+    ("rdquo", "”"),
+    ("bdquo", "„"),
+    ("laquo", "«"),
+    ("raquo", "»"),
+    ("middot", "·"),
+    ("bull", "•"),
+    ("dagger", "†"),
+    ("Dagger", "‡"),
+    ("para", "¶"),
+    ("sect", "§"),
+    ("copy", "©"),
+    ("reg", "®"),
+    ("trade", "™"),
+    ("deg", "°"),
+    ("prime", "′"),
+    ("Prime", "″"),
+    ("times", "×"),
+    ("divide", "÷"),
+    ("frac14", "¼"),
+    ("frac12", "½"),
+    ("frac34", "¾"),
+    ("aelig", "æ"),
+    ("AElig", "Æ"),
+    ("eacute", "é"),
+    ("Eacute", "É"),
+];
+
+/// Parses chapter or footnote HTML, and passes the document to `f` along
+/// with a description of what was wrong with it, if anything.
+///
+/// roxmltree only reads well-formed XML. HTML entities such as `&nbsp;` are
+/// replaced first, then if the document still can't be parsed its tags are
+/// balanced, and as a last resort its text is kept without any markup.
+pub(crate) fn with_document<T>(
+    html: &str,
+    f: impl FnOnce(Option<&Document>, Option<&str>) -> T,
+) -> T {
+    let html = replace_entities(html);
+    let error = match parse(&html) {
+        Ok(document) => return f(Some(&document), None),
+        Err(e) => e.to_string(),
+    };
+
+    let repaired = repair(&html);
+    if let Ok(document) = parse(&repaired) {
+        return f(Some(&document), Some(&error));
+    }
+    match parse(&plain_document(&html)) {
+        Ok(document) => f(Some(&document), Some(&error)),
+        Err(_) => f(None, Some(&error)),
+    }
+}
+
+fn parse(html: &str) -> Result<Document<'_>, roxmltree::Error> {
+    Document::parse_with_options(
+        html,
+        ParsingOptions {
+            allow_dtd: true,
+            ..Default::default()
+        },
+    )
+}
+
+/// Replaces HTML named character references with the characters they stand
+/// for, and escapes any `&` that doesn't start a reference.
+fn replace_entities(html: &str) -> Cow<'_, str> {
+    if !html.contains('&') {
+        return Cow::Borrowed(html);
+    }
+
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        let name = rest
+            .find(';')
+            .filter(|&end| end <= 32)
+            .map(|end| &rest[..end]);
+        match name {
+            Some(name)
+                if name.starts_with('#') || ["amp", "lt", "gt", "quot", "apos"].contains(&name) =>
+            {
+                out.push('&');
+            }
+            Some(name) => match ENTITIES.iter().find(|(entity, _)| *entity == name) {
+                Some((_, replacement)) => {
+                    out.push_str(replacement);
+                    rest = &rest[name.len() + 1..];
+                }
+                None => out.push_str("&amp;"),
+            },
+            None => out.push_str("&amp;"),
+        }
+    }
+    out.push_str(rest);
+    Cow::Owned(out)
+}
+
+/// Makes HTML well-formed: void elements are self-closed, attribute values
+/// quoted, end tags without a start tag dropped and unclosed elements closed.
+/// Element names are lowercased so that `<P>` and `</p>` match.
+fn repair(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut open: Vec<String> = vec![];
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        // Comments, doctypes and the like are kept as they are.
+        let special = [
+            ("<!--", "-->"),
+            ("<![CDATA[", "]]>"),
+            ("<?", "?>"),
+            ("<!", ">"),
+        ]
+        .iter()
+        .find(|(open, _)| rest.starts_with(open));
+        if let Some((_, close)) = special {
+            let end = rest.find(close).map_or(rest.len(), |end| end + close.len());
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+
+        let tag = rest.find('>').map(|end| &rest[1..end]);
+        let Some(tag) = tag.filter(|tag| !tag.contains('<')) else {
+            // Not a tag after all, such as the `<` in "a < b".
+            out.push_str("&lt;");
+            rest = &rest[1..];
+            continue;
+        };
+        rest = &rest[tag.len() + 2..];
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim().to_ascii_lowercase();
+            if let Some(i) = open.iter().rposition(|n| *n == name) {
+                for name in open.drain(i..).rev() {
+                    out.push_str(&format!("</{name}>"));
+                }
+            }
+            continue;
+        }
+
+        match start_tag(tag) {
+            Some(StartTag {
+                name,
+                attributes,
+                self_closing,
+            }) => {
+                out.push('<');
+                out.push_str(&name);
+                for (attribute, value) in attributes {
+                    let value = value.replace('"', "&quot;").replace('<', "&lt;");
+                    out.push_str(&format!(" {attribute}=\"{value}\""));
+                }
+                if self_closing || VOID_ELEMENTS.contains(&name.as_str()) {
+                    out.push_str("/>");
+                } else {
+                    out.push('>');
+                    open.push(name);
+                }
+            }
+            None => out.push_str(&format!("&lt;{tag}>")),
+        }
+    }
+    out.push_str(rest);
+    for name in open.into_iter().rev() {
+        out.push_str(&format!("</{name}>"));
+    }
+    out
+}
+
+/// A start tag read by [`start_tag`].
+struct StartTag<'a> {
+    /// Lowercased element name.
+    name: String,
+    attributes: Vec<(&'a str, &'a str)>,
+    self_closing: bool,
+}
+
+/// Reads the inside of a start tag. Attributes with invalid or repeated names
+/// are dropped.
+fn start_tag(tag: &str) -> Option<StartTag<'_>> {
+    let (tag, self_closing) = match tag.trim_end().strip_suffix('/') {
+        Some(tag) => (tag, true),
+        None => (tag, false),
+    };
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let name = &tag[..name_end];
+    if !is_name(name) {
+        return None;
+    }
+
+    let mut attributes: Vec<(&str, &str)> = vec![];
+    let mut rest = &tag[name_end..];
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(rest.len());
+        let attribute = &rest[..name_end];
+        rest = rest[name_end..].trim_start();
+
+        let mut value = "";
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let end = after[1..].find(quote).map_or(after.len(), |end| end + 1);
+                    value = &after[1..end];
+                    rest = &after[(end + 1).min(after.len())..];
+                }
+                _ => {
+                    let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                    value = &after[..end];
+                    rest = &after[end..];
+                }
+            }
+        }
+
+        if is_name(attribute) && !attributes.iter().any(|(a, _)| *a == attribute) {
+            attributes.push((attribute, value));
+        }
+    }
+    Some(StartTag {
+        name: name.to_ascii_lowercase(),
+        attributes,
+        self_closing,
+    })
+}
+
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ':' | '.'))
+}
+
+/// A document holding just the text of `html`, a paragraph for each `</p>`.
+fn plain_document(html: &str) -> String {
+    let mut body = String::new();
+    for part in html.split("</p>") {
+        let mut text = String::new();
+        let mut in_tag = false;
+        for c in part.chars() {
+            match c {
+                '<' => in_tag = true,
+                '>' if in_tag => {
+                    in_tag = false;
+                    text.push(' ');
+                }
+                '>' if !in_tag => text.push_str("&gt;"),
+                c if !in_tag => text.push(c),
+                _ => {}
+            }
+        }
+        if !text.trim().is_empty() {
+            body.push_str(&format!("<p>{}</p>", text.trim()));
+        }
+    }
+    format!("<html><body>{body}</body></html>")
+}
+
+/// Renders the `body` of a chapter's HTML: headings, paragraphs, lists,
+/// tables and figures, with each verse on a line of its own and the text
+/// covered by `annotations` marked. A `warning` from [`with_document`] is
+/// shown above the text.
+pub(crate) fn render_body(
+    body: Node,
+    annotations: &[Annotation],
+    warning: Option<&str>,
+) -> ChapterText {
+    let mut renderer = Renderer {
+        annotations,
+        text: Text::default(),
+        verses: vec![],
+        indent: String::new(),
+        marker: None,
+        after_heading: false,
+    };
+    if let Some(warning) = warning {
+        renderer.warning(warning);
+    }
+    renderer.blocks(body);
+    renderer.blank_line();
+    ChapterText {
+        text: renderer.text,
+        verses: renderer.verses,
+    }
+}
+
+/// Builds a [`ChapterText`] one block at a time, separating blocks with blank
+/// lines.
+struct Renderer<'a> {
+    annotations: &'a [Annotation],
+    text: Text<'static>,
+    verses: Vec<Verse>,
+    /// Indentation of the lines of the list item being rendered.
+    indent: String,
+    /// Marker and indentation for the first line of a list item, until it's
+    /// rendered.
+    marker: Option<String>,
+    /// Whether the last block was a centered heading. Headings that follow
+    /// one are stacked under it without a blank line.
+    after_heading: bool,
+}
+
+impl Renderer<'_> {
+    fn blocks(&mut self, node: Node) {
+        for child in node.children() {
+            self.block(child);
+        }
+    }
+
+    fn block(&mut self, node: Node) {
+        let italic = Style::default().add_modifier(Modifier::ITALIC);
+        if node.is_text() {
+            self.paragraph(node, Style::default(), None);
+            return;
+        }
+        if !node.is_element() {
+            return;
+        }
+
+        match node.tag_name().name() {
+            "head" | "script" | "style" | "img" => {}
+            "p" if has_class(node, "verse") => self.verse(node),
+            "h1" => self.heading(node, Some(Alignment::Center)),
+            "h2" | "h3" | "h4" | "h5" | "h6" => self.heading(node, None),
+            "p" if ["title", "title-number", "subtitle"]
+                .iter()
+                .any(|class| has_class(node, class)) =>
+            {
+                self.heading(node, Some(Alignment::Center))
+            }
+            "p" if has_class(node, "study-summary") => self.paragraph(node, italic, None),
+            "p" if has_class(node, "signature") => {
+                self.paragraph(node, Style::default(), Some(Alignment::Right))
+            }
+            "figcaption" | "caption" => self.paragraph(node, italic, None),
+            "ul" | "ol" => self.list(node),
+            "table" => self.table(node),
+            "figure" => self.figure(node),
+            tag if INLINE_ELEMENTS.contains(&tag) => self.paragraph(node, Style::default(), None),
+            // Containers such as `header`, `section` and `div`.
+            _ if node.children().any(is_block) => self.blocks(node),
+            _ => self.paragraph(node, Style::default(), None),
+        }
+    }
+
+    fn warning(&mut self, warning: &str) {
+        let style = Style::default().fg(Color::Yellow);
+        self.start_block(false);
+        self.push_line(Line::from(vec![
+            Span::styled("⚠ ", style.add_modifier(Modifier::BOLD)),
+            Span::styled(
+                "Part of this chapter couldn't be read, so some of it may be missing or out of place.",
+                style,
+            ),
+        ]));
+        self.push_line(Line::styled(
+            warning.to_string(),
+            style.add_modifier(Modifier::DIM),
+        ));
+    }
+
+    /// Separates the next block from the last one.
+    fn start_block(&mut self, heading: bool) {
+        if !(heading && self.after_heading) {
+            self.blank_line();
+        }
+        self.after_heading = heading;
+    }
+
+    /// Adds a blank line, unless the text is empty or already ends with one.
+    fn blank_line(&mut self) {
+        if self.text.lines.last().is_some_and(|l| l.width() > 0) {
+            self.text.lines.push(Line::default());
+        }
+    }
+
+    /// Adds a line of a block, indented to the list item it's in.
+    fn push_line(&mut self, mut line: Line<'static>) {
+        let prefix = self.marker.take().unwrap_or_else(|| self.indent.clone());
+        if !prefix.is_empty() {
+            line.spans.insert(0, Span::raw(prefix));
+        }
+        self.text.lines.push(line);
+    }
+
+    fn verse(&mut self, node: Node) {
+        let number = node
+            .children()
+            .find(|n| has_class(*n, "verse-number"))
+            .and_then(|n| n.text())
+            .and_then(|t| t.trim().parse().ok());
+        let aid = node.attribute("data-aid").unwrap_or_default();
+        let annotations = self
+            .annotations
+            .iter()
+            .filter(|a| a.paragraph_aid == aid)
+            .collect::<Vec<_>>();
+        let verse = verse_text(node, &annotations);
+
+        self.start_block(false);
+        self.verses.push(Verse {
+            number,
+            id: node.attribute("id").unwrap_or_default().to_string(),
+            aid: aid.to_string(),
+            line: self.text.lines.len(),
+            text: verse.words(),
+            markers: verse.markers,
+        });
+        // Verses are left unindented, as their markers count spans.
+        self.text.lines.push(verse.line);
+    }
+
+    fn heading(&mut self, node: Node, alignment: Option<Alignment>) {
+        let lines = inline_lines(node, Style::default().add_modifier(Modifier::BOLD));
+        if lines.is_empty() {
+            return;
+        }
+        self.start_block(alignment == Some(Alignment::Center));
+        for line in lines {
+            self.push_line(Line { alignment, ..line });
+        }
+    }
+
+    fn paragraph(&mut self, node: Node, style: Style, alignment: Option<Alignment>) {
+        let lines = inline_lines(node, style);
+        if lines.is_empty() {
+            return;
+        }
+        self.start_block(false);
+        for line in lines {
+            self.push_line(Line { alignment, ..line });
+        }
+    }
+
+    /// Renders each item of a `ul` or `ol` after its marker, which is the
+    /// item's `data-marker` when it has one.
+    fn list(&mut self, node: Node) {
+        let outer = self.indent.clone();
+        let items = node.children().filter(|n| n.has_tag_name("li"));
+        for (i, item) in items.enumerate() {
+            let marker = match item.attribute("data-marker") {
+                Some(marker) => marker.to_string(),
+                None if node.has_tag_name("ol") => format!("{}.", i + 1),
+                None => "•".to_string(),
+            };
+            let marker = format!("{outer}{marker} ");
+            self.indent = " ".repeat(Line::from(marker.as_str()).width());
+            self.marker = Some(marker);
+            if item.children().any(is_block) {
+                self.blocks(item);
+            } else {
+                self.paragraph(item, Style::default(), None);
+            }
+        }
+        self.indent = outer;
+        self.marker = None;
+    }
+
+    /// Renders a table with its columns aligned, its header cells in bold and
+    /// a rule under its header rows.
+    fn table(&mut self, node: Node) {
+        if let Some(caption) = node.children().find(|n| n.has_tag_name("caption")) {
+            self.block(caption);
+        }
+
+        let rows = node
+            .descendants()
+            .filter(|n| n.has_tag_name("tr"))
+            .map(|row| {
+                row.children()
+                    .filter(|n| n.has_tag_name("td") || n.has_tag_name("th"))
+                    .map(|cell| {
+                        let text = inline_lines(cell, Style::default())
+                            .iter()
+                            .map(line_text)
+                            .collect::<Vec<_>>()
+                            .join(" ");
+                        (text, cell.has_tag_name("th"))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .filter(|row| !row.is_empty())
+            .collect::<Vec<_>>();
+        if rows.is_empty() {
+            return;
+        }
+
+        let columns = rows.iter().map(Vec::len).max().unwrap_or_default();
+        let widths = (0..columns)
+            .map(|c| {
+                rows.iter()
+                    .filter_map(|row| row.get(c))
+                    .map(|(text, _)| Line::from(text.as_str()).width())
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+
+        self.start_block(false);
+        for row in &rows {
+            let mut spans = vec![];
+            for (c, (text, header)) in row.iter().enumerate() {
+                if c > 0 {
+                    spans.push(Span::raw(" │ "));
+                }
+                let padding = widths[c] - Line::from(text.as_str()).width();
+                let style = match header {
+                    true => Style::default().add_modifier(Modifier::BOLD),
+                    false => Style::default(),
+                };
+                spans.push(Span::styled(
+                    format!("{text}{}", " ".repeat(padding)),
+                    style,
+                ));
+            }
+            self.push_line(Line::from(spans));
+
+            if row.iter().all(|(_, header)| *header) {
+                let rule = widths
+                    .iter()
+                    .map(|w| "─".repeat(*w))
+                    .collect::<Vec<_>>()
+                    .join("─┼─");
+                self.push_line(Line::from(rule));
+            }
+        }
+    }
+
+    /// Renders a placeholder for a figure's image, followed by its caption
+    /// and any explanation that goes with it.
+    fn figure(&mut self, node: Node) {
+        if let Some(image) = node.descendants().find(|n| n.has_tag_name("img")) {
+            let label = match image.attribute("alt").filter(|alt| !alt.trim().is_empty()) {
+                Some(alt) => format!("[Image: {}]", alt.trim()),
+                None => "[Image]".to_string(),
+            };
+            self.start_block(false);
+            self.push_line(Line {
+                spans: vec![Span::styled(
+                    label,
+                    Style::default().add_modifier(Modifier::DIM | Modifier::ITALIC),
+                )],
+                alignment: Some(Alignment::Center),
+                ..Default::default()
+            });
+        }
+        self.blocks(node);
+    }
+}
+
+fn has_class(node: Node, class: &str) -> bool {
+    node.attribute("class")
+        .is_some_and(|classes| classes.split_whitespace().any(|c| c == class))
+}
+
+/// Whether `node` is an element that starts a block of its own.
+fn is_block(node: Node) -> bool {
+    node.is_element() && !INLINE_ELEMENTS.contains(&node.tag_name().name())
+}
+
+/// All the text within `node`.
+fn node_text(node: Node) -> String {
+    node.descendants()
+        .filter(|n| n.is_text())
+        .filter_map(|n| n.text())
+        .collect()
+}
+
+fn line_text(line: &Line) -> String {
+    line.spans.iter().map(|s| s.content.as_ref()).collect()
+}
+
+/// Renders the inline content of `node` with runs of whitespace collapsed,
+/// starting a new line at each `br`. Returns no lines if there is no text.
+fn inline_lines(node: Node, style: Style) -> Vec<Line<'static>> {
+    let mut lines = vec![Line::default()];
+    inline_spans(node, style, &mut lines);
+
+    for line in &mut lines {
+        if let Some(last) = line.spans.last_mut() {
+            let trimmed = last.content.trim_end().to_string();
+            last.content = trimmed.into();
+        }
+    }
+    let first = lines.iter().position(|l| l.width() > 0);
+    let last = lines.iter().rposition(|l| l.width() > 0);
+    match (first, last) {
+        (Some(first), Some(last)) => lines.drain(first..=last).collect(),
+        _ => vec![],
+    }
+}
+
+fn inline_spans(node: Node, style: Style, lines: &mut Vec<Line<'static>>) {
+    if node.is_text() {
+        if let (Some(text), Some(line)) = (node.text(), lines.last_mut()) {
+            push_collapsed(line, text, style);
+        }
+        return;
+    }
+
+    let style = match node.tag_name().name() {
+        "br" => {
+            lines.push(Line::default());
+            return;
+        }
+        "script" | "style" | "img" => return,
+        "sup"
+            if node
+                .parent()
+                .is_some_and(|p| has_class(p, "study-note-ref")) =>
+        {
+            if let (Some(marker), Some(line)) = (footnote_unicode(node.text()), lines.last_mut()) {
+                line.spans
+                    .push(Span::styled(marker, style.add_modifier(Modifier::ITALIC)));
+            }
+            return;
+        }
+        "em" | "i" | "cite" => style.add_modifier(Modifier::ITALIC),
+        "b" | "strong" => style.add_modifier(Modifier::BOLD),
+        "u" => style.add_modifier(Modifier::UNDERLINED),
+        _ => style,
+    };
+    for child in node.children() {
+        inline_spans(child, style, lines);
+    }
+}
+
+/// Adds `text` to `line` with each run of whitespace made a single space, and
+/// none at the start of the line.
+fn push_collapsed(line: &mut Line<'static>, text: &str, style: Style) {
+    let mut after_space = line
+        .spans
+        .last()
+        .is_none_or(|span| span.content.ends_with(' '));
+    let mut collapsed = String::new();
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !after_space {
+                collapsed.push(' ');
+            }
+            after_space = true;
+        } else {
+            collapsed.push(c);
+            after_space = false;
+        }
+    }
+    if !collapsed.is_empty() {
+        line.spans.push(Span::styled(collapsed, style));
+    }
+}
+
+/// A verse paragraph rendered to a single line.
+#[derive(Debug, Default)]
+struct VerseLine {
+    line: Line<'static>,
+    /// Index of the span holding the verse number, if there is one.
+    number_span: Option<usize>,
+    /// Where the footnote markers ended up.
+    markers: Vec<Marker>,
+}
+
+impl VerseLine {
+    /// The words of the verse, leaving out its number and the footnote
+    /// markers rendered as superscripts.
+    fn words(&self) -> String {
+        self.line
+            .spans
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| self.is_words(*i))
+            .map(|(_, span)| span.content.as_ref())
+            .collect()
+    }
+
+    /// Whether span `i` holds words of the verse, rather than its number or
+    /// a footnote marker.
+    fn is_words(&self, i: usize) -> bool {
+        self.number_span != Some(i) && !self.markers.iter().any(|m| m.span == i)
+    }
+}
+
+/// Renders a verse paragraph to a single line. Text covered by
+/// `annotations` is styled to match.
+fn verse_text(node: roxmltree::Node, annotations: &[&Annotation]) -> VerseLine {
+    let mut line = Line::default();
+    let mut markers = vec![];
+    let mut number_span = None;
+
+    for child in node.children() {
+        if child.attribute("class") == Some("verse-number") {
+            let verse_num_text = Span::styled(
+                node_text(child),
+                Style::default().add_modifier(Modifier::BOLD),
+            );
+            number_span = Some(line.spans.len());
+            line.spans.push(verse_num_text);
+        } else if child.attribute("class") == Some("para-mark") || child.is_text() {
+            line.spans.push(Span::raw(node_text(child)))
+        } else if child.attribute("class") == Some("clarity-word") {
+            if let Some(text) = child.text() {
+                // Sometimes clarity word just wraps some text.
+                let clarity_text = Span::styled(
+                    text.to_string(),
+                    Style::default().add_modifier(Modifier::ITALIC),
+                );
+                line.spans.push(clarity_text);
+            } else {
+                // Other times it wraps a reference.
+                verse_inline(
+                    child,
+                    Style::default().add_modifier(Modifier::ITALIC),
+                    &mut line,
+                    &mut markers,
+                );
+            }
+        } else if child.attribute("class") == Some("study-note-ref") {
+            study_note_ref(child, Style::default(), &mut line, &mut markers);
+        } else if child.is_element() {
+            // Keep the words of anything else, such as emphasis or small caps,
+            // and the footnote markers within them.
+            verse_inline(child, Style::default(), &mut line, &mut markers);
+        }
+    }
+
+    let mut verse = VerseLine {
+        line,
+        number_span,
+        markers,
+    };
+    if !annotations.is_empty() {
+        apply_annotations(&mut verse, annotations);
+    }
+    verse
+}
+
+/// Renders the contents of an element within a verse, such as emphasis, in
+/// `style` with the element's own style added.
+fn verse_inline(node: Node, style: Style, line: &mut Line<'static>, markers: &mut Vec<Marker>) {
+    let style = match node.tag_name().name() {
+        "em" | "i" => style.add_modifier(Modifier::ITALIC),
+        "b" | "strong" => style.add_modifier(Modifier::BOLD),
+        _ => style,
+    };
+    for child in node.children() {
+        if child.is_text() {
+            let text = node_text(child);
+            if !text.is_empty() {
+                line.spans.push(Span::styled(text, style));
+            }
+        } else if child.attribute("class") == Some("study-note-ref") {
+            study_note_ref(child, style, line, markers);
+        } else if child.is_element() {
+            verse_inline(child, style, line, markers);
+        }
+    }
+}
+
+/// Renders a reference to a footnote: its marker as a superscript, followed
+/// by the words it's on in `style`.
+fn study_note_ref(node: Node, style: Style, line: &mut Line<'static>, markers: &mut Vec<Marker>) {
+    for child in node.children() {
+        if child.tag_name().name() == "sup" {
+            if let Some(footnote) = footnote_unicode(child.text()) {
+                markers.push(Marker {
+                    ref_id: node.attribute("data-ref").unwrap_or_default().into(),
+                    span: line.spans.len(),
+                });
+                line.spans
+                    .push(Span::styled(footnote, style.add_modifier(Modifier::ITALIC)));
+            }
+        } else if child.is_text() {
+            line.spans.push(Span::styled(node_text(child), style));
+        } else if child.is_element() {
+            verse_inline(child, style, line, markers);
+        }
+    }
+}
+
+/// Splits the spans of a verse's line where annotations start and end, and
+/// adds each annotation's style to the parts it covers. The verse number and
+/// footnote markers don't count toward the annotations' offsets.
+fn apply_annotations(verse: &mut VerseLine, annotations: &[&Annotation]) {
+    let mut boundaries = annotations
+        .iter()
+        .flat_map(|a| [a.start_index, a.end_index])
+        .collect::<Vec<_>>();
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    let mut spans = vec![];
+    // Where each of the original spans starts among the new ones.
+    let mut positions = vec![];
+    let mut offset = 0;
+    for (i, span) in std::mem::take(&mut verse.line.spans)
+        .into_iter()
+        .enumerate()
+    {
+        positions.push(spans.len());
+        if !verse.is_words(i) {
+            spans.push(span);
+            continue;
+        }
+
+        let chars = span.content.chars().collect::<Vec<_>>();
+        let end = offset + chars.len();
+        let mut start = offset;
+        for &boundary in boundaries.iter().filter(|&&b| b > offset && b < end) {
+            spans.push(annotated_span(
+                &chars[start - offset..boundary - offset],
+                start,
+                span.style,
+                annotations,
+            ));
+            start = boundary;
+        }
+        spans.push(annotated_span(
+            &chars[start - offset..],
+            start,
+            span.style,
+            annotations,
+        ));
+        offset = end;
+    }
+
+    verse.line.spans = spans;
+    verse.number_span = verse.number_span.map(|i| positions[i]);
+    for marker in &mut verse.markers {
+        marker.span = positions[marker.span];
+    }
+}
+
+/// A piece of verse text starting at character `offset`, with the style of
+/// every annotation covering it added to `style`.
+fn annotated_span(
+    chars: &[char],
+    offset: usize,
+    style: Style,
+    annotations: &[&Annotation],
+) -> Span<'static> {
+    let style = annotations
+        .iter()
+        .filter(|a| a.range().contains(&offset))
+        .fold(style, |style, a| style.patch(a.style()));
+    Span::styled(chars.iter().collect::<String>(), style)
+}
+
+fn footnote_unicode(string: Option<&str>) -> Option<&'static str> {
+    let input = string?;
+    match input {
+        "a" => Some("ᵃ"),
+        "b" => Some("ᵇ"),
+        "c" => Some("ᶜ"),
+        "d" => Some("ᵈ"),
+        "e" => Some("ᵉ"),
+        "f" => Some("ᶠ"),
+        "g" => Some("ᵍ"),
+        "h" => Some("ʰ"),
+        "i" => Some("ⁱ"),
+        "j" => Some("ʲ"),
+        "k" => Some("ᵏ"),
+        "l" => Some("ˡ"),
+        "m" => Some("ᵐ"),
+        "n" => Some("ⁿ"),
+        "o" => Some("ᵒ"),
+        "p" => Some("ᵖ"),
+        "q" => Some("q"),
+        "r" => Some("ʳ"),
+        "s" => Some("ˢ"),
+        "t" => Some("ᵗ"),
+        "u" => Some("ᵘ"),
+        "v" => Some("ᵛ"),
+        "w" => Some("ʷ"),
+        "x" => Some("ˣ"),
+        "y" => Some("ʸ"),
+        "z" => Some("ᶻ"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use super::*;
+    use crate::user_data::AnnotationKind;
+
+    /// Renders the first verse paragraph of `html` with `annotations`.
+    fn render_verse(html: &str, annotations: &[&Annotation]) -> VerseLine {
+        let document = parse(html).unwrap();
+        let verse = document
+            .descendants()
+            .find(|n| has_class(*n, "verse"))
+            .unwrap();
+        verse_text(verse, annotations)
+    }
+
+    const EMPHASIS_VERSE: &str = r#"<p class="verse" data-aid="1" id="p17"><span class="verse-number">17 </span>pointing to the other—<em>This is My <span class="study-note-ref" data-ref="note17f"><sup class="marker">f</sup>Beloved</span> <span class="study-note-ref" data-ref="note17g"><sup class="marker">g</sup>Son</span>. Hear Him!</em></p>"#;
+
+    #[test]
+    fn markers_inside_emphasis_stay_markers() {
+        let verse = render_verse(EMPHASIS_VERSE, &[]);
+
+        assert_eq!(
+            verse.words(),
+            "pointing to the other—This is My Beloved Son. Hear Him!"
+        );
+        assert_eq!(
+            line_text(&verse.line),
+            "17 pointing to the other—This is My ᶠBeloved ᵍSon. Hear Him!"
+        );
+        let refs = verse
+            .markers
+            .iter()
+            .map(|m| m.ref_id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(refs, ["note17f", "note17g"]);
+        for marker in &verse.markers {
+            assert!(matches!(
+                verse.line.spans[marker.span].content.as_ref(),
+                "ᶠ" | "ᵍ"
+            ));
+        }
+    }
+
+    #[test]
+    fn words_inside_emphasis_keep_its_style() {
+        let verse = render_verse(EMPHASIS_VERSE, &[]);
+        let beloved = &verse.line.spans[verse.markers[0].span + 1];
+
+        assert_eq!(beloved.content, "Beloved");
+        assert!(beloved.style.add_modifier.contains(Modifier::ITALIC));
+    }
+
+    fn annotation(kind: AnnotationKind, range: Range<usize>) -> Annotation {
+        Annotation {
+            id: 0,
+            work_uri: String::new(),
+            chapter_id: String::new(),
+            paragraph_aid: "1".into(),
+            start_index: range.start,
+            end_index: range.end,
+            kind,
+            color: "yellow".into(),
+        }
+    }
+
+    /// The words of the verse whose style `matches`.
+    fn words_where(verse: &VerseLine, matches: impl Fn(Style) -> bool) -> String {
+        verse
+            .line
+            .spans
+            .iter()
+            .enumerate()
+            .filter(|(i, span)| verse.is_words(*i) && matches(span.style))
+            .map(|(_, span)| span.content.as_ref())
+            .collect()
+    }
+
+    fn highlighted(style: Style) -> bool {
+        style.bg == Some(Color::Yellow)
+    }
+
+    fn underlined(style: Style) -> bool {
+        style.add_modifier.contains(Modifier::UNDERLINED)
+    }
+
+    fn italic(style: Style) -> bool {
+        style.add_modifier.contains(Modifier::ITALIC)
+    }
+
+    #[test]
+    fn highlights_split_styled_spans() {
+        // "the other—" is plain and "This is My " is in emphasis.
+        let highlight = annotation(AnnotationKind::Highlight, 12..25);
+        let verse = render_verse(EMPHASIS_VERSE, &[&highlight]);
+
+        assert_eq!(words_where(&verse, highlighted), "the other—Thi");
+        assert_eq!(words_where(&verse, |s| highlighted(s) && italic(s)), "Thi");
+        assert_eq!(
+            words_where(&verse, |s| !highlighted(s) && italic(s)),
+            "s is My Beloved Son. Hear Him!"
+        );
+    }
+
+    #[test]
+    fn highlights_skip_markers() {
+        // "My Beloved Son", with a marker before each of the last two words.
+        let highlight = annotation(AnnotationKind::Highlight, 30..44);
+        let verse = render_verse(EMPHASIS_VERSE, &[&highlight]);
+
+        assert_eq!(words_where(&verse, highlighted), "My Beloved Son");
+        assert_eq!(
+            line_text(&verse.line),
+            "17 pointing to the other—This is My ᶠBeloved ᵍSon. Hear Him!"
+        );
+        for (marker, label) in verse.markers.iter().zip(["ᶠ", "ᵍ"]) {
+            let span = &verse.line.spans[marker.span];
+            assert_eq!(span.content, label);
+            assert!(!highlighted(span.style));
+        }
+    }
+
+    #[test]
+    fn overlapping_annotations_combine() {
+        // "pointing to the" and "to the other—".
+        let highlight = annotation(AnnotationKind::Highlight, 0..15);
+        let underline = annotation(AnnotationKind::Underline, 9..22);
+        let verse = render_verse(EMPHASIS_VERSE, &[&highlight, &underline]);
+
+        assert_eq!(
+            words_where(&verse, |s| highlighted(s) && !underlined(s)),
+            "pointing "
+        );
+        assert_eq!(
+            words_where(&verse, |s| highlighted(s) && underlined(s)),
+            "to the"
+        );
+        assert_eq!(
+            words_where(&verse, |s| !highlighted(s) && underlined(s)),
+            " other—"
+        );
+    }
+
+    #[test]
+    fn offsets_past_the_end_are_ignored() {
+        // The verse's words are 55 characters long.
+        let past_end = annotation(AnnotationKind::Highlight, 51..80);
+        let beyond = annotation(AnnotationKind::Underline, 70..90);
+        let verse = render_verse(EMPHASIS_VERSE, &[&past_end, &beyond]);
+
+        assert_eq!(words_where(&verse, highlighted), "Him!");
+        assert_eq!(words_where(&verse, underlined), "");
+        assert_eq!(
+            verse.words(),
+            "pointing to the other—This is My Beloved Son. Hear Him!"
+        );
+    }
+
+    #[test]
+    fn bold_words_without_a_verse_number_count_as_words() {
+        let html = r#"<p class="verse" data-aid="2"><b>Chapter</b> heading and more</p>"#;
+        let highlight = annotation(AnnotationKind::Highlight, 0..15);
+        let verse = render_verse(html, &[&highlight]);
+
+        assert_eq!(verse.number_span, None);
+        assert_eq!(verse.words(), "Chapter heading and more");
+        assert_eq!(words_where(&verse, highlighted), "Chapter heading");
+    }
+
+    #[test]
+    fn verse_numbers_after_other_text_move_with_annotations() {
+        let html = r#"<p class="verse" data-aid="3">¶ <span class="verse-number">3 </span>And it came to pass</p>"#;
+        let highlight = annotation(AnnotationKind::Highlight, 1..2);
+        let verse = render_verse(html, &[&highlight]);
+
+        assert_eq!(verse.words(), "¶ And it came to pass");
+        assert_eq!(words_where(&verse, highlighted), " ");
+        let number = verse.number_span.unwrap();
+        assert_eq!(verse.line.spans[number].content, "3 ");
+    }
+}
@@ -0,0 +1,875 @@
+use std::fmt;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+
+use crate::config::Config;
+
+/// Largest count that can be typed before an action.
+const MAX_COUNT: usize = 9999;
+
+/// Something a key binding does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    Cancel,
+    Clear,
+    Help,
+    Up,
+    Down,
+    FocusLeft,
+    FocusRight,
+    NextWork,
+    PrevWork,
+    NextBook,
+    PrevBook,
+    NextChapter,
+    PrevChapter,
+    NextVerse,
+    PrevVerse,
+    ExtendNextVerse,
+    ExtendPrevVerse,
+    NextFootnote,
+    PrevFootnote,
+    NextLink,
+    PrevLink,
+    FollowLink,
+    Back,
+    Forward,
+    Bookmark,
+    Bookmarks,
+    Highlight,
+    Underline,
+    RemoveAnnotations,
+    ToggleNotes,
+    EditNote,
+    Goto,
+    Search,
+    PageDown,
+    PageUp,
+    HalfPageDown,
+    HalfPageUp,
+    LineDown,
+    LineUp,
+    Top,
+    Bottom,
+    ReaderMode,
+}
+
+impl Action {
+    /// Every action, in the order the help lists them.
+    pub const ALL: [Action; 43] = [
+        Action::Help,
+        Action::Quit,
+        Action::Cancel,
+        Action::Clear,
+        Action::Up,
+        Action::Down,
+        Action::FocusLeft,
+        Action::FocusRight,
+        Action::NextWork,
+        Action::PrevWork,
+        Action::NextBook,
+        Action::PrevBook,
+        Action::NextChapter,
+        Action::PrevChapter,
+        Action::ReaderMode,
+        Action::PageDown,
+        Action::PageUp,
+        Action::HalfPageDown,
+        Action::HalfPageUp,
+        Action::LineDown,
+        Action::LineUp,
+        Action::Top,
+        Action::Bottom,
+        Action::NextVerse,
+        Action::PrevVerse,
+        Action::ExtendNextVerse,
+        Action::ExtendPrevVerse,
+        Action::NextFootnote,
+        Action::PrevFootnote,
+        Action::NextLink,
+        Action::PrevLink,
+        Action::FollowLink,
+        Action::Back,
+        Action::Forward,
+        Action::Goto,
+        Action::Search,
+        Action::Bookmark,
+        Action::Bookmarks,
+        Action::Highlight,
+        Action::Underline,
+        Action::RemoveAnnotations,
+        Action::ToggleNotes,
+        Action::EditNote,
+    ];
+
+    /// Name of the action in the config file, such as "next-chapter".
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Cancel => "cancel",
+            Action::Clear => "clear",
+            Action::Help => "help",
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::FocusLeft => "focus-left",
+            Action::FocusRight => "focus-right",
+            Action::NextWork => "next-work",
+            Action::PrevWork => "prev-work",
+            Action::NextBook => "next-book",
+            Action::PrevBook => "prev-book",
+            Action::NextChapter => "next-chapter",
+            Action::PrevChapter => "prev-chapter",
+            Action::NextVerse => "next-verse",
+            Action::PrevVerse => "prev-verse",
+            Action::ExtendNextVerse => "extend-next-verse",
+            Action::ExtendPrevVerse => "extend-prev-verse",
+            Action::NextFootnote => "next-footnote",
+            Action::PrevFootnote => "prev-footnote",
+            Action::NextLink => "next-link",
+            Action::PrevLink => "prev-link",
+            Action::FollowLink => "follow-link",
+            Action::Back => "back",
+            Action::Forward => "forward",
+            Action::Bookmark => "bookmark",
+            Action::Bookmarks => "bookmarks",
+            Action::Highlight => "highlight",
+            Action::Underline => "underline",
+            Action::RemoveAnnotations => "remove-annotations",
+            Action::ToggleNotes => "toggle-notes",
+            Action::EditNote => "edit-note",
+            Action::Goto => "goto",
+            Action::Search => "search",
+            Action::PageDown => "page-down",
+            Action::PageUp => "page-up",
+            Action::HalfPageDown => "half-page-down",
+            Action::HalfPageUp => "half-page-up",
+            Action::LineDown => "line-down",
+            Action::LineUp => "line-up",
+            Action::Top => "top",
+            Action::Bottom => "bottom",
+            Action::ReaderMode => "reader-mode",
+        }
+    }
+
+    /// What the action does, for the help.
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::Cancel => "Deselect, close the notes, or quit",
+            Action::Clear => "Deselect, or close the notes",
+            Action::Help => "Show the key bindings",
+            Action::Up => "Previous list item, or scroll up",
+            Action::Down => "Next list item, or scroll down",
+            Action::FocusLeft => "Focus the column to the left",
+            Action::FocusRight => "Focus the column to the right",
+            Action::NextWork => "Next work",
+            Action::PrevWork => "Previous work",
+            Action::NextBook => "Next book",
+            Action::PrevBook => "Previous book",
+            Action::NextChapter => "Next chapter",
+            Action::PrevChapter => "Previous chapter",
+            Action::NextVerse => "Next verse",
+            Action::PrevVerse => "Previous verse",
+            Action::ExtendNextVerse => "Extend the selection down",
+            Action::ExtendPrevVerse => "Extend the selection up",
+            Action::NextFootnote => "Read the verse's next footnote",
+            Action::PrevFootnote => "Read the verse's previous footnote",
+            Action::NextLink => "Select the next cross-reference",
+            Action::PrevLink => "Select the previous cross-reference",
+            Action::FollowLink => "Follow the selected cross-reference",
+            Action::Back => "Go back",
+            Action::Forward => "Go forward",
+            Action::Bookmark => "Bookmark the verse or chapter",
+            Action::Bookmarks => "List the bookmarks",
+            Action::Highlight => "Highlight the selected verses",
+            Action::Underline => "Underline the selected verses",
+            Action::RemoveAnnotations => "Remove highlights and underlines",
+            Action::ToggleNotes => "Show or hide the note panel",
+            Action::EditNote => "Edit the verse's note",
+            Action::Goto => "Go to a reference",
+            Action::Search => "Search",
+            Action::PageDown => "Scroll down a page",
+            Action::PageUp => "Scroll up a page",
+            Action::HalfPageDown => "Scroll down half a page",
+            Action::HalfPageUp => "Scroll up half a page",
+            Action::LineDown => "Scroll down a line",
+            Action::LineUp => "Scroll up a line",
+            Action::Top => "Scroll to the top, or verse N after a count",
+            Action::Bottom => "Scroll to the bottom, or verse N after a count",
+            Action::ReaderMode => "Read the chapters as one document, without the lists",
+        }
+    }
+
+    /// Looks up an action by its [`Action::name`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.name() == name)
+    }
+
+    /// Is the action done again for each time a count before it asks?
+    /// Opening prompts and panels isn't.
+    pub fn repeats(self) -> bool {
+        matches!(
+            self,
+            Action::Up
+                | Action::Down
+                | Action::FocusLeft
+                | Action::FocusRight
+                | Action::NextWork
+                | Action::PrevWork
+                | Action::NextBook
+                | Action::PrevBook
+                | Action::NextChapter
+                | Action::PrevChapter
+                | Action::NextVerse
+                | Action::PrevVerse
+                | Action::ExtendNextVerse
+                | Action::ExtendPrevVerse
+                | Action::NextFootnote
+                | Action::PrevFootnote
+                | Action::NextLink
+                | Action::PrevLink
+                | Action::Back
+                | Action::Forward
+                | Action::PageDown
+                | Action::PageUp
+                | Action::HalfPageDown
+                | Action::HalfPageUp
+                | Action::LineDown
+                | Action::LineUp
+        )
+    }
+}
+
+/// A key pressed together with any modifiers, such as `ctrl-d`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let mut modifiers =
+            modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        // Shift is already part of the character, and of `BackTab`.
+        if matches!(code, KeyCode::Char(_) | KeyCode::BackTab) {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Self { code, modifiers }
+    }
+
+    /// Parses a chord such as "j", "G", "ctrl-d", "alt-<", "pagedown" or
+    /// "shift-tab".
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (modifier_names, key) = match s.rfind('-') {
+            // A chord ending in "--" is the `-` key with modifiers.
+            _ if s.len() > 1 && s.ends_with("--") => (&s[..s.len() - 2], "-"),
+            Some(i) if i > 0 && i + 1 < s.len() => (&s[..i], &s[i + 1..]),
+            _ => ("", s),
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        for name in modifier_names.split('-').filter(|n| !n.is_empty()) {
+            modifiers |= match name.to_lowercase().as_str() {
+                "ctrl" | "c" => KeyModifiers::CONTROL,
+                "alt" | "meta" | "a" | "m" => KeyModifiers::ALT,
+                "shift" | "s" => KeyModifiers::SHIFT,
+                _ => return Err(format!("Unknown modifier \"{name}\" in \"{s}\"")),
+            };
+        }
+
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => {
+                KeyCode::Char(c.to_ascii_uppercase())
+            }
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match key.to_lowercase().as_str() {
+                "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+                "esc" | "escape" => KeyCode::Esc,
+                "enter" | "return" => KeyCode::Enter,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" | "ins" => KeyCode::Insert,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "space" => KeyCode::Char(' '),
+                "minus" => KeyCode::Char('-'),
+                name => match name.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                    Some(n @ 1..=24) => KeyCode::F(n),
+                    _ => return Err(format!("Unknown key \"{key}\"")),
+                },
+            },
+        };
+        Ok(Self::new(code, modifiers))
+    }
+
+    /// Is this a character typed without modifiers, which sequences of are
+    /// written without spaces?
+    fn is_plain_char(&self) -> bool {
+        matches!(self.code, KeyCode::Char(c) if c != ' ') && self.modifiers.is_empty()
+    }
+}
+
+impl From<KeyEvent> for KeyChord {
+    fn from(event: KeyEvent) -> Self {
+        Self::new(event.code, event.modifiers)
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "alt-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "shift-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::F(n) => write!(f, "f{n}"),
+            code => {
+                let name = match code {
+                    KeyCode::Esc => "esc",
+                    KeyCode::Enter => "enter",
+                    KeyCode::Tab => "tab",
+                    KeyCode::BackTab => "shift-tab",
+                    KeyCode::Backspace => "backspace",
+                    KeyCode::Delete => "delete",
+                    KeyCode::Insert => "insert",
+                    KeyCode::Home => "home",
+                    KeyCode::End => "end",
+                    KeyCode::PageUp => "pageup",
+                    KeyCode::PageDown => "pagedown",
+                    KeyCode::Up => "up",
+                    KeyCode::Down => "down",
+                    KeyCode::Left => "left",
+                    KeyCode::Right => "right",
+                    _ => "?",
+                };
+                write!(f, "{name}")
+            }
+        }
+    }
+}
+
+/// Parses a sequence of chords separated by spaces, such as "ctrl-x ctrl-c".
+/// Characters without modifiers can also be run together, as in "gg" or
+/// "]b".
+pub fn parse_keys(s: &str) -> Result<Vec<KeyChord>, String> {
+    let mut keys = vec![];
+    for word in s.split_whitespace() {
+        match KeyChord::parse(word) {
+            Ok(chord) => keys.push(chord),
+            Err(_) if !word.contains('-') => keys.extend(
+                word.chars()
+                    .map(|c| KeyChord::new(KeyCode::Char(c), KeyModifiers::NONE)),
+            ),
+            Err(e) => return Err(e),
+        }
+    }
+    if keys.is_empty() {
+        return Err("Empty key binding".to_string());
+    }
+    Ok(keys)
+}
+
+/// Writes a sequence the way [`parse_keys`] reads it.
+pub fn format_keys(keys: &[KeyChord]) -> String {
+    let separator = if keys.iter().all(KeyChord::is_plain_char) {
+        ""
+    } else {
+        " "
+    };
+    keys.iter()
+        .map(|k| k.to_string())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// A set of bindings to start from.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    /// Arrows to move around the lists and panes, and a letter for
+    /// everything else.
+    #[default]
+    Default,
+    /// The default keys, with `hjkl`, `gg`/`G` and `]`/`[` motions.
+    Vim,
+    /// The default keys, with `ctrl-n`/`ctrl-p`, `ctrl-v`/`alt-v` and other
+    /// Emacs keys.
+    Emacs,
+}
+
+impl Preset {
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Default => "default",
+            Preset::Vim => "vim",
+            Preset::Emacs => "emacs",
+        }
+    }
+
+    /// Bindings the preset adds to or replaces in [`DEFAULT_KEYS`].
+    fn keys(self) -> &'static [(&'static str, Action)] {
+        match self {
+            Preset::Default => &[],
+            Preset::Vim => VIM_KEYS,
+            Preset::Emacs => EMACS_KEYS,
+        }
+    }
+}
+
+const DEFAULT_KEYS: &[(&str, Action)] = &[
+    ("?", Action::Help),
+    ("q", Action::Quit),
+    ("esc", Action::Cancel),
+    ("up", Action::Up),
+    ("down", Action::Down),
+    ("left", Action::FocusLeft),
+    ("right", Action::FocusRight),
+    (">", Action::NextChapter),
+    ("<", Action::PrevChapter),
+    ("}", Action::NextBook),
+    ("{", Action::PrevBook),
+    ("r", Action::ReaderMode),
+    ("pagedown", Action::PageDown),
+    ("pageup", Action::PageUp),
+    ("ctrl-d", Action::HalfPageDown),
+    ("ctrl-u", Action::HalfPageUp),
+    ("ctrl-e", Action::LineDown),
+    ("ctrl-y", Action::LineUp),
+    ("home", Action::Top),
+    ("end", Action::Bottom),
+    ("j", Action::NextVerse),
+    ("k", Action::PrevVerse),
+    ("J", Action::ExtendNextVerse),
+    ("K", Action::ExtendPrevVerse),
+    ("f", Action::NextFootnote),
+    ("F", Action::PrevFootnote),
+    ("]", Action::NextLink),
+    ("[", Action::PrevLink),
+    ("enter", Action::FollowLink),
+    ("backspace", Action::Back),
+    ("ctrl-o", Action::Back),
+    // Most terminals send `ctrl-i` as `tab`.
+    ("ctrl-i", Action::Forward),
+    ("tab", Action::Forward),
+    (":", Action::Goto),
+    ("/", Action::Search),
+    ("b", Action::Bookmark),
+    ("B", Action::Bookmarks),
+    ("h", Action::Highlight),
+    ("u", Action::Underline),
+    ("x", Action::RemoveAnnotations),
+    ("n", Action::ToggleNotes),
+    ("e", Action::EditNote),
+];
+
+const VIM_KEYS: &[(&str, Action)] = &[
+    ("h", Action::FocusLeft),
+    ("l", Action::FocusRight),
+    ("k", Action::Up),
+    ("j", Action::Down),
+    ("gg", Action::Top),
+    ("G", Action::Bottom),
+    ("ctrl-f", Action::PageDown),
+    ("ctrl-b", Action::PageUp),
+    ("}", Action::NextVerse),
+    ("{", Action::PrevVerse),
+    ("]]", Action::NextChapter),
+    ("[[", Action::PrevChapter),
+    ("]b", Action::NextBook),
+    ("[b", Action::PrevBook),
+    ("]w", Action::NextWork),
+    ("[w", Action::PrevWork),
+    ("]l", Action::NextLink),
+    ("[l", Action::PrevLink),
+    ("m", Action::Highlight),
+];
+
+const EMACS_KEYS: &[(&str, Action)] = &[
+    ("ctrl-p", Action::Up),
+    ("ctrl-n", Action::Down),
+    ("ctrl-b", Action::FocusLeft),
+    ("ctrl-f", Action::FocusRight),
+    ("ctrl-v", Action::PageDown),
+    ("alt-v", Action::PageUp),
+    ("alt-<", Action::Top),
+    ("alt->", Action::Bottom),
+    ("alt-n", Action::NextVerse),
+    ("alt-p", Action::PrevVerse),
+    ("ctrl-x ]", Action::NextChapter),
+    ("ctrl-x [", Action::PrevChapter),
+    ("ctrl-s", Action::Search),
+    ("alt-g g", Action::Goto),
+    ("alt-g alt-g", Action::Goto),
+    ("ctrl-g", Action::Clear),
+    ("ctrl-x ctrl-c", Action::Quit),
+];
+
+/// Key sequences bound to actions, and the keys typed so far towards one.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    pub preset: Preset,
+    bindings: Vec<(Vec<KeyChord>, Action)>,
+    /// Keys typed that start one or more sequences.
+    pending: Vec<KeyChord>,
+    /// Number typed before the keys, as in `5j`.
+    count: Option<usize>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::new(Preset::Default)
+    }
+}
+
+impl Keymap {
+    /// The bindings of `preset`.
+    pub fn new(preset: Preset) -> Self {
+        let mut keymap = Self {
+            preset,
+            bindings: vec![],
+            pending: vec![],
+            count: None,
+        };
+        for (keys, action) in DEFAULT_KEYS.iter().chain(preset.keys()) {
+            if let Ok(keys) = parse_keys(keys) {
+                keymap.bind(keys, Some(*action));
+            }
+        }
+        keymap
+    }
+
+    /// The preset chosen in `config` with its bindings applied, and a
+    /// message for each binding that couldn't be.
+    pub fn from_config(config: &Config) -> (Self, Vec<String>) {
+        let mut keymap = Self::new(config.keymap);
+        let mut errors = vec![];
+        for (keys, action_name) in &config.keys {
+            let action = match action_name.as_str() {
+                "none" => None,
+                name => match Action::from_name(name) {
+                    Some(action) => Some(action),
+                    None => {
+                        errors.push(format!("Unknown action \"{name}\""));
+                        continue;
+                    }
+                },
+            };
+            match parse_keys(keys) {
+                Ok(keys) => keymap.bind(keys, action),
+                Err(e) => errors.push(e),
+            }
+        }
+        (keymap, errors)
+    }
+
+    /// Binds `keys` to `action`, or unbinds them. Bindings that start with
+    /// `keys`, or that `keys` start with, are removed, since one of them
+    /// could never be typed.
+    pub fn bind(&mut self, keys: Vec<KeyChord>, action: Option<Action>) {
+        self.bindings
+            .retain(|(bound, _)| !bound.starts_with(&keys) && !keys.starts_with(bound));
+        if let Some(action) = action {
+            self.bindings.push((keys, action));
+        }
+    }
+
+    /// Takes a key press. Returns the action once a whole sequence has been
+    /// typed, along with the count typed before it.
+    pub fn press(&mut self, event: KeyEvent) -> Option<(Action, Option<usize>)> {
+        let chord = KeyChord::from(event);
+
+        // `esc`, or a key bound to cancel or clear, abandons a half-typed
+        // sequence.
+        let aborts = chord.code == KeyCode::Esc
+            || self.bindings.iter().any(|(keys, action)| {
+                *keys == [chord] && matches!(action, Action::Cancel | Action::Clear)
+            });
+        if aborts && (!self.pending.is_empty() || self.count.is_some()) {
+            self.reset();
+            return None;
+        }
+
+        // Digits that aren't bound themselves make up a count, although it
+        // can't start with 0.
+        if let KeyCode::Char(c @ '0'..='9') = chord.code
+            && chord.modifiers.is_empty()
+            && self.pending.is_empty()
+            && (c != '0' || self.count.is_some())
+            && !self.bindings.iter().any(|(keys, _)| keys[0] == chord)
+        {
+            let digit = c as usize - '0' as usize;
+            self.count = Some((self.count.unwrap_or_default() * 10 + digit).min(MAX_COUNT));
+            return None;
+        }
+
+        self.pending.push(chord);
+        if let Some((_, action)) = self.bindings.iter().find(|(keys, _)| *keys == self.pending) {
+            let action = *action;
+            let count = self.count;
+            self.reset();
+            return Some((action, count));
+        }
+        if !self
+            .bindings
+            .iter()
+            .any(|(keys, _)| keys.starts_with(&self.pending))
+        {
+            self.reset();
+        }
+        None
+    }
+
+    fn reset(&mut self) {
+        self.pending.clear();
+        self.count = None;
+    }
+
+    /// The count and keys typed so far, such as "5g", while in the middle of
+    /// a sequence.
+    pub fn pending(&self) -> Option<String> {
+        if self.pending.is_empty() && self.count.is_none() {
+            return None;
+        }
+        let count = self.count.map(|c| c.to_string()).unwrap_or_default();
+        Some(format!("{count}{}", format_keys(&self.pending)))
+    }
+
+    /// The first sequence bound to `action`, to name in hints.
+    pub fn key_for(&self, action: Action) -> Option<String> {
+        self.bindings
+            .iter()
+            .find(|(_, a)| *a == action)
+            .map(|(keys, _)| format_keys(keys))
+    }
+
+    /// Every action that's bound, with the sequences bound to it, in the
+    /// order of [`Action::ALL`].
+    pub fn bindings(&self) -> Vec<(Action, Vec<String>)> {
+        Action::ALL
+            .into_iter()
+            .filter_map(|action| {
+                let keys = self
+                    .bindings
+                    .iter()
+                    .filter(|(_, a)| *a == action)
+                    .map(|(keys, _)| format_keys(keys))
+                    .collect::<Vec<_>>();
+                (!keys.is_empty()).then_some((action, keys))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    fn ctrl(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    /// Presses `keys` in turn, returning what the last one did.
+    fn press(keymap: &mut Keymap, keys: &[KeyEvent]) -> Option<(Action, Option<usize>)> {
+        let (last, first) = keys.split_last().unwrap();
+        for event in first {
+            assert_eq!(keymap.press(*event), None);
+        }
+        keymap.press(*last)
+    }
+
+    #[test]
+    fn parses_chords() {
+        assert_eq!(
+            KeyChord::parse("alt-n"),
+            Ok(KeyChord::new(KeyCode::Char('n'), KeyModifiers::ALT))
+        );
+        assert_eq!(
+            KeyChord::parse("G"),
+            Ok(KeyChord::new(KeyCode::Char('G'), KeyModifiers::NONE))
+        );
+        assert_eq!(KeyChord::parse("shift-g"), KeyChord::parse("G"));
+        assert_eq!(
+            KeyChord::parse("ctrl--"),
+            Ok(KeyChord::new(KeyCode::Char('-'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            KeyChord::parse("pagedown"),
+            Ok(KeyChord::new(KeyCode::PageDown, KeyModifiers::NONE))
+        );
+        assert_eq!(
+            KeyChord::parse("shift-tab"),
+            Ok(KeyChord::new(KeyCode::BackTab, KeyModifiers::NONE))
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        assert!(KeyChord::parse("pgdn").is_err());
+        assert!(KeyChord::parse("hyper-x").is_err());
+        assert!(parse_keys("ctrl-foo").is_err());
+        assert!(parse_keys("  ").is_err());
+    }
+
+    #[test]
+    fn parses_sequences() {
+        let ctrl_x = KeyChord::new(KeyCode::Char('x'), KeyModifiers::CONTROL);
+        let ctrl_c = KeyChord::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert_eq!(parse_keys("ctrl-x ctrl-c"), Ok(vec![ctrl_x, ctrl_c]));
+        assert_eq!(parse_keys("gg"), parse_keys("g g"));
+        assert_eq!(format_keys(&parse_keys("g g").unwrap()), "gg");
+        assert_eq!(
+            format_keys(&parse_keys("ctrl-x ctrl-c").unwrap()),
+            "ctrl-x ctrl-c"
+        );
+    }
+
+    #[test]
+    fn events_match_parsed_chords() {
+        let shifted = KeyEvent::new(KeyCode::Char('G'), KeyModifiers::SHIFT);
+        assert_eq!(KeyChord::from(shifted), KeyChord::parse("G").unwrap());
+        assert_eq!(
+            KeyChord::from(ctrl('d')),
+            KeyChord::parse("ctrl-d").unwrap()
+        );
+    }
+
+    #[test]
+    fn counts_repeat_keys() {
+        let mut keymap = Keymap::new(Preset::Vim);
+        assert_eq!(
+            press(&mut keymap, &[key('5'), key('j')]),
+            Some((Action::Down, Some(5)))
+        );
+        assert_eq!(
+            press(&mut keymap, &[key('1'), key('0'), key('j')]),
+            Some((Action::Down, Some(10)))
+        );
+        // The count is used up.
+        assert_eq!(keymap.press(key('j')), Some((Action::Down, None)));
+    }
+
+    #[test]
+    fn counts_go_with_top_and_bottom() {
+        let mut keymap = Keymap::new(Preset::Vim);
+        assert_eq!(
+            press(&mut keymap, &[key('5'), key('G')]),
+            Some((Action::Bottom, Some(5)))
+        );
+        assert_eq!(
+            press(&mut keymap, &[key('5'), key('g'), key('g')]),
+            Some((Action::Top, Some(5)))
+        );
+    }
+
+    #[test]
+    fn sequences_wait_for_their_last_key() {
+        let mut keymap = Keymap::new(Preset::Vim);
+        assert_eq!(keymap.press(key('g')), None);
+        assert_eq!(keymap.pending().as_deref(), Some("g"));
+        assert_eq!(keymap.press(key('g')), Some((Action::Top, None)));
+        assert_eq!(keymap.pending(), None);
+    }
+
+    #[test]
+    fn keys_that_dont_continue_a_sequence_reset_it() {
+        let mut keymap = Keymap::new(Preset::Vim);
+        assert_eq!(press(&mut keymap, &[key('3'), key('g'), key('z')]), None);
+        assert_eq!(keymap.pending(), None);
+        // `j` after the abandoned `3gz` has no count.
+        assert_eq!(keymap.press(key('j')), Some((Action::Down, None)));
+    }
+
+    #[test]
+    fn escape_abandons_a_sequence() {
+        let mut keymap = Keymap::new(Preset::Vim);
+        let esc = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(press(&mut keymap, &[key('g'), esc]), None);
+        assert_eq!(keymap.pending(), None);
+        assert_eq!(keymap.press(esc), Some((Action::Cancel, None)));
+    }
+
+    #[test]
+    fn presets_replace_conflicting_defaults() {
+        let mut keymap = Keymap::new(Preset::Vim);
+        // `]` selects a link by default, but starts `]]` in vim.
+        assert_eq!(
+            press(&mut keymap, &[key(']'), key(']')]),
+            Some((Action::NextChapter, None))
+        );
+        assert_eq!(
+            press(&mut keymap, &[key(']'), key('l')]),
+            Some((Action::NextLink, None))
+        );
+
+        let mut keymap = Keymap::new(Preset::Emacs);
+        assert_eq!(keymap.press(ctrl('g')), Some((Action::Clear, None)));
+        assert_eq!(
+            press(&mut keymap, &[ctrl('x'), ctrl('c')]),
+            Some((Action::Quit, None))
+        );
+    }
+
+    #[test]
+    fn config_overrides_the_preset() {
+        let config = Config {
+            keymap: Preset::Vim,
+            keys: BTreeMap::from([
+                ("j".to_string(), "next-verse".to_string()),
+                ("G".to_string(), "none".to_string()),
+                ("ctrl-n".to_string(), "next-chapter".to_string()),
+            ]),
+            ..Default::default()
+        };
+        let (mut keymap, errors) = Keymap::from_config(&config);
+
+        assert!(errors.is_empty());
+        assert_eq!(keymap.press(key('j')), Some((Action::NextVerse, None)));
+        assert_eq!(keymap.press(key('G')), None);
+        assert_eq!(keymap.press(ctrl('n')), Some((Action::NextChapter, None)));
+        // The rest of the preset is still there.
+        assert_eq!(keymap.press(key('k')), Some((Action::Up, None)));
+    }
+
+    #[test]
+    fn config_errors_are_reported() {
+        let config = Config {
+            keys: BTreeMap::from([
+                ("x".to_string(), "explode".to_string()),
+                ("hyper-x".to_string(), "quit".to_string()),
+            ]),
+            ..Default::default()
+        };
+        let (keymap, errors) = Keymap::from_config(&config);
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            keymap.key_for(Action::RemoveAnnotations).as_deref(),
+            Some("x")
+        );
+    }
+}
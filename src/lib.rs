@@ -12,3 +12,45 @@ pub mod tui;
 
 /// Event handler.
 pub mod handler;
+
+/// Scripture data loading and rendering.
+pub mod scriptures;
+
+/// Rendering chapter HTML to text.
+pub mod html;
+
+/// Command line arguments.
+pub mod cli;
+
+/// Settings read from the config file.
+pub mod config;
+
+/// Key bindings.
+pub mod keymap;
+
+/// File system locations.
+pub mod paths;
+
+/// Scripture reference parsing.
+pub mod reference;
+
+/// Full-text search.
+pub mod search;
+
+/// Back and forward navigation history.
+pub mod history;
+
+/// Session state saved between runs.
+pub mod state;
+
+/// Bookmarks, annotations and notes belonging to the reader.
+pub mod user_data;
+
+/// Multiline text editing.
+pub mod editor;
+
+/// Exporting and importing the reader's data.
+pub mod export;
+
+/// Printing passages outside the reader.
+pub mod show;
@@ -1,16 +1,53 @@
+use clap::Parser;
 use scripture_tui::app::{App, AppResult};
+use scripture_tui::cli::{Cli, Command};
+use scripture_tui::config::Config;
 use scripture_tui::event::{Event, EventHandler};
+use scripture_tui::export;
 use scripture_tui::handler::{handle_key_events, handle_mouse_events};
-use scripture_tui::tui::Tui;
-use std::io;
-use tui::backend::CrosstermBackend;
+use scripture_tui::paths;
+use scripture_tui::scriptures::Scriptures;
+use scripture_tui::search;
+use scripture_tui::show;
+use scripture_tui::state::SessionState;
+use scripture_tui::tui::{Tui, install_panic_hook};
+use std::io::{self, Stderr};
+use std::path::Path;
+use std::process;
 use tui::Terminal;
+use tui::backend::CrosstermBackend;
 
 fn main() -> AppResult<()> {
+    let cli = Cli::parse();
+
+    let data_dir = paths::data_dir(cli.data_dir.as_deref());
+    if let Some(command) = cli.command {
+        // Report errors plainly rather than as `Debug` output.
+        if let Err(e) = run_command(command, &data_dir) {
+            // Output piped into `head` and the like may stop being read early.
+            if e.downcast_ref::<io::Error>()
+                .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+            {
+                return Ok(());
+            }
+            eprintln!("scripture-tui: {e}");
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
     // Create an application.
-    let mut app = App::new();
+    let mut app = App::new(Scriptures::load(&data_dir));
+    match Config::load() {
+        Ok(config) => app.configure(&config),
+        Err(e) => app.status = Some(format!("Unable to read config: {e}")),
+    }
+    if let Some(state) = SessionState::load() {
+        app.restore_session(&state);
+    }
 
     // Initialize the terminal user interface.
+    install_panic_hook();
     let mut tui = Tui::new(
         Terminal::new(CrosstermBackend::new(io::stderr()))?,
         EventHandler::new(250),
@@ -18,19 +55,54 @@ fn main() -> AppResult<()> {
     tui.init()?;
 
     // Start the main loop.
+    let result = run(&mut app, &mut tui);
+
+    // Exit the user interface, before any error is printed.
+    tui.exit()?;
+
+    // Remember where we were for next time.
+    if let Err(e) = app.session_state().save() {
+        eprintln!("Unable to save session state: {e}");
+    }
+    result
+}
+
+/// Runs the main loop until the user quits.
+fn run(app: &mut App, tui: &mut Tui<CrosstermBackend<Stderr>>) -> AppResult<()> {
     while app.running {
         // Render the user interface.
-        tui.draw(&mut app)?;
+        tui.draw(app)?;
         // Handle events.
         match tui.events.next()? {
             Event::Tick => app.tick(),
-            Event::Key(key_event) => handle_key_events(key_event, &mut app)?,
-            Event::Mouse(mouse_event) => handle_mouse_events(mouse_event, &mut app)?,
+            Event::Key(key_event) => handle_key_events(key_event, app)?,
+            Event::Mouse(mouse_event) => handle_mouse_events(mouse_event, app)?,
             Event::Resize(_, _) => {}
         }
+        // Hand the terminal to `$EDITOR` to edit a note.
+        if let Some(edit) = app.external_edit.take() {
+            let result = tui.edit_externally(&edit.editor, &edit.text);
+            app.finish_external_edit(result);
+        }
     }
-
-    // Exit the user interface.
-    tui.exit()?;
     Ok(())
 }
+
+/// Runs a subcommand instead of the user interface.
+fn run_command(command: Command, data_dir: &Path) -> AppResult<()> {
+    match command {
+        Command::Show {
+            reference,
+            footnotes,
+        } => show::show(data_dir, &reference, footnotes),
+        Command::Search {
+            query,
+            work,
+            mode,
+            case_sensitive,
+            json,
+        } => search::grep(data_dir, &query, mode, &work, case_sensitive, json),
+        Command::Export { format, output } => export::export(data_dir, format, output.as_deref()),
+        Command::Import { file } => export::import(&file),
+    }
+}
@@ -0,0 +1,71 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use crate::scriptures;
+
+/// Name of the application's directory inside the XDG base directories.
+const APP_DIR: &str = "scripture-tui";
+
+/// Finds the directory holding the scripture databases.
+///
+/// An explicitly requested directory always wins. Otherwise the XDG data
+/// directories are searched, followed by the current directory, and the first
+/// one containing a `*.sqlite` file is used.
+pub fn data_dir(explicit: Option<&Path>) -> PathBuf {
+    if let Some(dir) = explicit {
+        return dir.to_path_buf();
+    }
+
+    let current_dir = PathBuf::from(".");
+    data_dirs()
+        .into_iter()
+        .find(|dir| scriptures::has_databases(dir))
+        .unwrap_or(current_dir)
+}
+
+/// `$XDG_DATA_HOME/scripture-tui` followed by each entry of `$XDG_DATA_DIRS`,
+/// then the current directory.
+fn data_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    dirs.extend(user_data_dir());
+
+    let system_dirs = env::var_os("XDG_DATA_DIRS")
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".into());
+    dirs.extend(env::split_paths(&system_dirs).map(|d| d.join(APP_DIR)));
+
+    dirs.push(PathBuf::from("."));
+    dirs
+}
+
+/// `$XDG_DATA_HOME/scripture-tui`, for data belonging to the reader such as
+/// bookmarks.
+pub fn user_data_dir() -> Option<PathBuf> {
+    xdg_home("XDG_DATA_HOME", ".local/share").map(|dir| dir.join(APP_DIR))
+}
+
+/// `$XDG_CACHE_HOME/scripture-tui`, for files that can be rebuilt at any time.
+pub fn cache_dir() -> Option<PathBuf> {
+    xdg_home("XDG_CACHE_HOME", ".cache").map(|dir| dir.join(APP_DIR))
+}
+
+/// `$XDG_CONFIG_HOME/scripture-tui`, for settings written by the reader.
+pub fn config_dir() -> Option<PathBuf> {
+    xdg_home("XDG_CONFIG_HOME", ".config").map(|dir| dir.join(APP_DIR))
+}
+
+/// `$XDG_STATE_HOME/scripture-tui`, for state kept between sessions.
+pub fn state_dir() -> Option<PathBuf> {
+    xdg_home("XDG_STATE_HOME", ".local/state").map(|dir| dir.join(APP_DIR))
+}
+
+/// Resolves an XDG base directory variable, falling back to `fallback` under
+/// the home directory when it is unset or not absolute.
+fn xdg_home(var: &str, fallback: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| env::home_dir().map(|home| home.join(fallback)))
+}
@@ -0,0 +1,386 @@
+use std::ops::RangeInclusive;
+
+use crate::app::AppResult;
+use crate::scriptures::{Location, Scriptures};
+
+/// Abbreviations that aren't simply a prefix of the book's title, already
+/// [`normalize`]d.
+const ALIASES: &[(&str, &str)] = &[
+    ("a of f", "articles of faith"),
+    ("d&c", "doctrine and covenants"),
+    ("dc", "doctrine and covenants"),
+    ("js-h", "joseph smith-history"),
+    ("js-m", "joseph smith-matthew"),
+    ("1 jn", "1 john"),
+    ("2 jn", "2 john"),
+    ("3 jn", "3 john"),
+    ("jn", "john"),
+    ("lk", "luke"),
+    ("mk", "mark"),
+    ("mt", "matthew"),
+    ("w of m", "words of mormon"),
+];
+
+/// A parsed scripture reference such as `1 Ne 3:7` or `D&C 121:7-8, 10`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    /// Book name or abbreviation as typed.
+    pub book: String,
+    pub chapter: Option<u32>,
+    /// Verse ranges in the order given. Single verses are one-element ranges.
+    pub verses: Vec<RangeInclusive<u32>>,
+}
+
+impl Reference {
+    /// Parses `<book> [<chapter>[:<verses>]]`, where verses is a comma
+    /// separated list of verses (`21`) and ranges (`7-8`).
+    pub fn parse(input: &str) -> AppResult<Self> {
+        let input = input.trim();
+
+        // The chapter and verses are the trailing run of digits and separators.
+        // Whatever precedes it, such as "1 Ne" or "D&C", is the book.
+        let numbers_start = input
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| c.is_ascii_digit() || " :,-–".contains(*c))
+            .last()
+            .map(|(i, _)| i)
+            .unwrap_or(input.len());
+        let (book, numbers) = match input[..numbers_start].trim() {
+            // Books such as "1 Nephi" start with a number. Without a chapter
+            // there's nothing after the book for the numbers to be.
+            "" => (input, ""),
+            book => (book, input[numbers_start..].trim()),
+        };
+        if book.is_empty() {
+            return Err("Expected a book name".into());
+        }
+
+        let (chapter, verses) = match numbers.split_once(':') {
+            Some((chapter, verses)) => (chapter, Some(verses)),
+            None => (numbers, None),
+        };
+        let chapter = match chapter.trim() {
+            "" => None,
+            chapter => Some(parse_number(chapter)?),
+        };
+
+        let mut verse_ranges = vec![];
+        for part in verses.unwrap_or_default().split(',') {
+            let part = part.trim();
+            if part.is_empty() {
+                continue;
+            }
+
+            let range = match part.split_once(['-', '–']) {
+                Some((start, end)) => parse_number(start)?..=parse_number(end)?,
+                None => {
+                    let verse = parse_number(part)?;
+                    verse..=verse
+                }
+            };
+            if range.is_empty() {
+                return Err(format!("Verse range {part} is backwards").into());
+            }
+            verse_ranges.push(range);
+        }
+        if verses.is_some() && verse_ranges.is_empty() {
+            return Err("Expected a verse after ':'".into());
+        }
+
+        Ok(Reference {
+            book: book.to_string(),
+            chapter,
+            verses: verse_ranges,
+        })
+    }
+
+    /// The first verse referred to, if any.
+    pub fn first_verse(&self) -> Option<u32> {
+        self.verses.first().map(|r| *r.start())
+    }
+
+    /// Finds the chapter this reference points to among the loaded works.
+    pub fn resolve(&self, scriptures: &Scriptures) -> AppResult<Location> {
+        // A chapter titled exactly as typed, such as "Facsimile 1", whose
+        // number isn't a chapter of a book of that name.
+        let title = match self.chapter {
+            Some(chapter) => normalize(&format!("{} {chapter}", self.book)),
+            None => normalize(&self.book),
+        };
+        for (work_index, work) in scriptures.works.iter().enumerate() {
+            for (book_index, book) in work.books.iter().enumerate() {
+                if let Some(chapter) = book
+                    .chapters
+                    .iter()
+                    .position(|c| normalize(&c.title) == title)
+                {
+                    return Ok(Location {
+                        work: work_index,
+                        book: book_index,
+                        chapter,
+                    });
+                }
+            }
+        }
+
+        let name = normalize(&self.book);
+        let name = ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == name)
+            .map(|(_, full)| full.to_string())
+            .unwrap_or(name);
+
+        // Every (work, book) pair the name could refer to, best match first:
+        // an exact book title, then a whole work, then a book title prefix.
+        let books = |matches: &dyn Fn(usize, usize) -> bool| {
+            let mut found = vec![];
+            for (w, work) in scriptures.works.iter().enumerate() {
+                for b in 0..work.books.len() {
+                    if matches(w, b) {
+                        found.push((w, b));
+                    }
+                }
+            }
+            found
+        };
+        let book_title = |w: usize, b: usize| normalize(&scriptures.works[w].books[b].title);
+        let work_matches = |w: usize| {
+            let work = &scriptures.works[w];
+            normalize(&work.title) == name || normalize(&work.abbreviation) == name
+        };
+
+        let candidates = [
+            books(&|w, b| book_title(w, b) == name),
+            books(&|w, _| work_matches(w)),
+            books(&|w, b| book_title(w, b).starts_with(&name)),
+        ];
+        let candidates = candidates
+            .into_iter()
+            .find(|c| !c.is_empty())
+            .ok_or_else(|| format!("No book matches {}", self.book))?;
+
+        let Some(chapter) = self.chapter else {
+            let (work, book) = candidates[0];
+            return Ok(Location {
+                work,
+                book,
+                chapter: 0,
+            });
+        };
+
+        // Chapters are titled "Alma 32" or "Section 121", so match on the
+        // trailing number rather than position, which intro pages and
+        // facsimiles would skew.
+        for &(work, book) in &candidates {
+            let chapters = &scriptures.works[work].books[book].chapters;
+            if let Some(i) = chapters.iter().position(|c| {
+                c.title
+                    .rsplit(' ')
+                    .next()
+                    .and_then(|n| n.parse::<u32>().ok())
+                    == Some(chapter)
+            }) {
+                return Ok(Location {
+                    work,
+                    book,
+                    chapter: i,
+                });
+            }
+        }
+
+        let (work, book) = candidates[0];
+        let title = &scriptures.works[work].books[book].title;
+        Err(format!("{title} has no chapter {chapter}").into())
+    }
+}
+
+fn parse_number(s: &str) -> AppResult<u32> {
+    s.trim()
+        .parse()
+        .map_err(|_| format!("Expected a number, found '{}'", s.trim()).into())
+}
+
+/// Lowercases a book name and removes the punctuation differences between
+/// abbreviations and titles, so "1Ne." and "1 Nephi" can be compared.
+fn normalize(name: &str) -> String {
+    let mut normalized = String::new();
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        let c = match c {
+            '.' => continue,
+            '—' | '–' => '-',
+            c if c.is_whitespace() => ' ',
+            c => c.to_lowercase().next().unwrap_or(c),
+        };
+
+        // "1Ne" is written "1 Ne" in titles.
+        if previous.is_some_and(|p| p.is_ascii_digit()) && c.is_alphabetic() {
+            normalized.push(' ');
+        }
+        if c == ' ' && previous == Some(' ') {
+            continue;
+        }
+
+        normalized.push(c);
+        previous = Some(c);
+    }
+    normalized.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scriptures::{Book, ChapterEntry, Work};
+
+    fn work(title: &str, abbreviation: &str, books: &[(&str, &[&str])]) -> Work {
+        let mut work = Work::default();
+        work.title = title.to_string();
+        work.abbreviation = abbreviation.to_string();
+        work.books = books
+            .iter()
+            .map(|(title, chapters)| Book {
+                title: title.to_string(),
+                chapters: chapters
+                    .iter()
+                    .map(|title| ChapterEntry {
+                        title: title.to_string(),
+                        ..Default::default()
+                    })
+                    .collect(),
+            })
+            .collect();
+        work
+    }
+
+    fn scriptures() -> Scriptures {
+        let mut scriptures = Scriptures::default();
+        scriptures.works = vec![
+            work(
+                "New Testament",
+                "NT",
+                &[
+                    ("John", &["John 1", "John 2"]),
+                    ("1 John", &["1 John 1", "1 John 4"]),
+                ],
+            ),
+            work(
+                "Book of Mormon",
+                "BoM",
+                &[
+                    ("1 Nephi", &["1 Nephi 1", "1 Nephi 2", "1 Nephi 3"]),
+                    ("Alma", &["Alma 31", "Alma 32"]),
+                ],
+            ),
+            work(
+                "Doctrine and Covenants",
+                "D&C",
+                &[("Sections", &["Section 120", "Section 121"])],
+            ),
+            work(
+                "Pearl of Great Price",
+                "PoGP",
+                &[
+                    ("Moses", &["Introductory Note", "Moses 1"]),
+                    ("Abraham", &["Abraham 1", "Abraham 2", "Facsimile 1"]),
+                ],
+            ),
+        ];
+        scriptures
+    }
+
+    fn resolve(input: &str) -> Option<Location> {
+        Reference::parse(input).unwrap().resolve(&scriptures()).ok()
+    }
+
+    fn location(work: usize, book: usize, chapter: usize) -> Option<Location> {
+        Some(Location {
+            work,
+            book,
+            chapter,
+        })
+    }
+
+    #[test]
+    fn parses_a_verse() {
+        let reference = Reference::parse("Alma 32:21").unwrap();
+        assert_eq!(reference.book, "Alma");
+        assert_eq!(reference.chapter, Some(32));
+        assert_eq!(reference.verses, vec![21..=21]);
+        assert_eq!(reference.first_verse(), Some(21));
+    }
+
+    #[test]
+    fn parses_books_starting_with_a_number() {
+        for input in ["1 Ne 3:7", "1Ne. 3:7"] {
+            let reference = Reference::parse(input).unwrap();
+            assert_eq!(reference.chapter, Some(3), "{input}");
+            assert_eq!(reference.verses, vec![7..=7], "{input}");
+            assert_eq!(resolve(input), location(1, 0, 2), "{input}");
+        }
+    }
+
+    #[test]
+    fn parses_verse_lists() {
+        let reference = Reference::parse("D&C 121:7-8, 10").unwrap();
+        assert_eq!(reference.book, "D&C");
+        assert_eq!(reference.chapter, Some(121));
+        assert_eq!(reference.verses, vec![7..=8, 10..=10]);
+    }
+
+    #[test]
+    fn parses_whole_chapters_and_books() {
+        let reference = Reference::parse("Alma 32").unwrap();
+        assert_eq!(reference.chapter, Some(32));
+        assert!(reference.verses.is_empty());
+        assert_eq!(reference.first_verse(), None);
+
+        let reference = Reference::parse("1 Nephi").unwrap();
+        assert_eq!(reference.book, "1 Nephi");
+        assert_eq!(reference.chapter, None);
+    }
+
+    #[test]
+    fn rejects_malformed_references() {
+        assert!(Reference::parse("Alma 32:9-7").is_err());
+        assert!(Reference::parse("Alma 32:").is_err());
+        assert!(Reference::parse("").is_err());
+    }
+
+    #[test]
+    fn resolves_chapters_by_title_number() {
+        assert_eq!(resolve("Alma 32:21"), location(1, 1, 1));
+        assert_eq!(resolve("D&C 121:7-8, 10"), location(2, 0, 1));
+        assert_eq!(resolve("Moses 1:39"), location(3, 0, 1));
+        assert_eq!(resolve("Moses"), location(3, 0, 0));
+    }
+
+    #[test]
+    fn resolves_abbreviations() {
+        assert_eq!(resolve("Jn 2"), location(0, 0, 1));
+        assert_eq!(resolve("1 Jn 4:8"), location(0, 1, 1));
+        assert_eq!(resolve("1Jn. 4:8"), location(0, 1, 1));
+    }
+
+    #[test]
+    fn resolves_whole_chapter_titles() {
+        assert_eq!(resolve("Facsimile 1"), location(3, 1, 2));
+        assert_eq!(resolve("facsimile 1:3"), location(3, 1, 2));
+        assert_eq!(resolve("Introductory Note"), location(3, 0, 0));
+    }
+
+    #[test]
+    fn unknown_books_and_chapters_dont_resolve() {
+        let error = |input| {
+            Reference::parse(input)
+                .unwrap()
+                .resolve(&scriptures())
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(error("Hezekiah 1:1"), "No book matches Hezekiah");
+        assert_eq!(error("Alma 99"), "Alma has no chapter 99");
+        // Not the third page of the book, which is a facsimile.
+        assert_eq!(error("Abraham 3"), "Abraham has no chapter 3");
+    }
+}
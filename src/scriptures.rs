@@ -0,0 +1,626 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
+use tui::{
+    style::{Modifier, Style},
+    text::{Line, Span, Text},
+};
+
+use crate::app::AppResult;
+use crate::html;
+use crate::user_data::Annotation;
+
+/// URIs of the standard works, in the order they are listed.
+const CANONICAL_ORDER: &[&str] = &[
+    "/scriptures/ot",
+    "/scriptures/nt",
+    "/scriptures/bofm",
+    "/scriptures/dc-testament",
+    "/scriptures/pgp",
+];
+
+/// How many parsed chapters are kept in memory at once.
+const CHAPTER_CACHE_CAPACITY: usize = 32;
+
+#[derive(Debug, Clone)]
+struct SqliteRow {
+    id: String,
+    chapter_title: String,
+    uri: String,
+    book_title: String,
+}
+
+/// Every work found in the data directory.
+///
+/// Only the Work → Book → Chapter index is read up front. Chapter content is
+/// fetched on demand through [`Scriptures::chapter`].
+#[derive(Debug, Default)]
+pub struct Scriptures {
+    pub(crate) works: Vec<Work>,
+    /// Directory the works were loaded from.
+    pub(crate) data_dir: PathBuf,
+    cache: ChapterCache,
+}
+
+impl Scriptures {
+    /// Loads every `*.sqlite` database in `data_dir`. Databases that are
+    /// corrupt are kept in the list, marked with the error that prevented loading.
+    pub fn load(data_dir: &Path) -> Self {
+        let mut works: Vec<Work> = database_paths(data_dir)
+            .iter()
+            .map(|db| {
+                Work::load(db).unwrap_or_else(|e| {
+                    let file_name = db.file_name().unwrap_or_default().to_string_lossy();
+                    Work {
+                        title: file_name.to_string(),
+                        abbreviation: file_name.trim_end_matches(".sqlite").to_string(),
+                        error: Some(format!("{file_name}: {e}")),
+                        path: db.clone(),
+                        ..Default::default()
+                    }
+                })
+            })
+            .collect();
+
+        works.sort_by_key(|w| {
+            let position = CANONICAL_ORDER.iter().position(|uri| *uri == w.uri);
+            (position.unwrap_or(CANONICAL_ORDER.len()), w.title.clone())
+        });
+
+        Scriptures {
+            works,
+            data_dir: data_dir.to_path_buf(),
+            cache: ChapterCache::default(),
+        }
+    }
+
+    /// The chapter at the given indices, read from the database unless it was
+    /// recently used.
+    pub(crate) fn chapter(
+        &mut self,
+        work: usize,
+        book: usize,
+        chapter: usize,
+    ) -> AppResult<Option<Rc<Chapter>>> {
+        let Some(work) = self.works.get(work) else {
+            return Ok(None);
+        };
+        let Some(entry) = work.books.get(book).and_then(|b| b.chapters.get(chapter)) else {
+            return Ok(None);
+        };
+
+        let key = (work.uri.clone(), entry.id.clone());
+        if let Some(chapter) = self.cache.get(&key) {
+            return Ok(Some(chapter));
+        }
+
+        let chapter = Rc::new(work.load_chapter(entry)?);
+        self.cache.insert(key, chapter.clone());
+        Ok(Some(chapter))
+    }
+
+    /// Finds a chapter by the URI of its work and its `subitem.id`.
+    pub(crate) fn locate(&self, work_uri: &str, chapter_id: &str) -> Option<Location> {
+        let work = self.works.iter().position(|w| w.uri == work_uri)?;
+        self.works[work]
+            .books
+            .iter()
+            .enumerate()
+            .find_map(|(book, b)| {
+                let chapter = b.chapters.iter().position(|c| c.id == chapter_id)?;
+                Some(Location {
+                    work,
+                    book,
+                    chapter,
+                })
+            })
+    }
+
+    /// Finds a chapter by its content URI, such as "/scriptures/bofm/1-ne/17".
+    pub(crate) fn locate_uri(&self, uri: &str) -> Option<Location> {
+        self.works.iter().enumerate().find_map(|(work, w)| {
+            w.books.iter().enumerate().find_map(|(book, b)| {
+                let chapter = b.chapters.iter().position(|c| c.uri == uri)?;
+                Some(Location {
+                    work,
+                    book,
+                    chapter,
+                })
+            })
+        })
+    }
+
+    /// The chapter read after `location`, or before it. The last chapter of a
+    /// book is followed by the first of the next book, and the last book of a
+    /// work by the first book of the next work, so Malachi 4 leads to
+    /// Matthew 1.
+    pub(crate) fn adjacent_chapter(&self, location: Location, forward: bool) -> Option<Location> {
+        let chapter_count = |work: usize, book: usize| {
+            self.works
+                .get(work)
+                .and_then(|w| w.books.get(book))
+                .map_or(0, |b| b.chapters.len())
+        };
+
+        let Location {
+            work,
+            book,
+            chapter,
+        } = location;
+        if forward && chapter + 1 < chapter_count(work, book) {
+            return Some(Location {
+                chapter: chapter + 1,
+                ..location
+            });
+        }
+        if !forward && chapter > 0 {
+            return Some(Location {
+                chapter: chapter - 1,
+                ..location
+            });
+        }
+
+        // Every book with chapters, in reading order.
+        let books = self
+            .works
+            .iter()
+            .enumerate()
+            .flat_map(|(w, work)| (0..work.books.len()).map(move |b| (w, b)))
+            .filter(|&(w, b)| chapter_count(w, b) > 0)
+            .collect::<Vec<_>>();
+        let i = books.iter().position(|&b| b == (work, book))?;
+        let (work, book) = if forward {
+            *books.get(i + 1)?
+        } else {
+            *books.get(i.checked_sub(1)?)?
+        };
+        let chapter = if forward {
+            0
+        } else {
+            chapter_count(work, book) - 1
+        };
+        Some(Location {
+            work,
+            book,
+            chapter,
+        })
+    }
+
+    /// Errors for every work that failed to load.
+    pub(crate) fn errors(&self) -> Vec<&str> {
+        self.works
+            .iter()
+            .filter_map(|w| w.error.as_deref())
+            .collect()
+    }
+}
+
+/// Whether `dir` contains at least one scripture database.
+pub fn has_databases(dir: &Path) -> bool {
+    !database_paths(dir).is_empty()
+}
+
+fn database_paths(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+
+    let mut paths = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && p.extension() == Some(OsStr::new("sqlite")))
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
+
+/// Shortens a work title to its initials, e.g. "Book of Mormon" to "BoM" and
+/// "Doctrine and Covenants" to "D&C".
+fn abbreviate(title: &str) -> String {
+    title
+        .split_whitespace()
+        .filter_map(|word| match word {
+            "and" => Some("&".to_string()),
+            "of" | "the" => word.chars().next().map(String::from),
+            _ => word.chars().next().map(|c| c.to_uppercase().to_string()),
+        })
+        .collect()
+}
+
+/// Position of a chapter within [`Scriptures`]. Locations order the same
+/// way the chapters are read.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Location {
+    pub work: usize,
+    pub book: usize,
+    pub chapter: usize,
+}
+
+/// Least recently used cache of loaded chapters, keyed by work URI and subitem id.
+#[derive(Debug, Default)]
+struct ChapterCache {
+    /// Most recently used last.
+    entries: VecDeque<((String, String), Rc<Chapter>)>,
+}
+
+impl ChapterCache {
+    fn get(&mut self, key: &(String, String)) -> Option<Rc<Chapter>> {
+        let i = self.entries.iter().position(|(k, _)| k == key)?;
+        let entry = self.entries.remove(i)?;
+        let chapter = entry.1.clone();
+        self.entries.push_back(entry);
+        Some(chapter)
+    }
+
+    fn insert(&mut self, key: (String, String), chapter: Rc<Chapter>) {
+        if self.entries.len() == CHAPTER_CACHE_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((key, chapter));
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Work {
+    /// Full title, such as "Pearl of Great Price".
+    pub(crate) title: String,
+    /// Short title shown in the Work column, such as "PoGP".
+    pub(crate) abbreviation: String,
+    /// Content URI of the work, such as "/scriptures/pgp".
+    pub(crate) uri: String,
+    pub(crate) books: Vec<Book>,
+    /// Why this work could not be loaded, if it couldn't.
+    pub(crate) error: Option<String>,
+    /// Database file the work was read from.
+    pub(crate) path: PathBuf,
+    conn: Option<Connection>,
+}
+
+impl Work {
+    fn load(db: &Path) -> AppResult<Self> {
+        // Open read-only so a missing file is reported instead of created empty.
+        let conn = Connection::open_with_flags(db, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+        let metadata = |key: &str| {
+            conn.query_row("SELECT value FROM metadata WHERE key = ?", [key], |row| {
+                row.get::<_, String>(0)
+            })
+            .optional()
+        };
+        let root_collection = conn
+            .query_row(
+                "SELECT title, uri FROM nav_collection WHERE nav_section_id IS NULL ORDER BY position LIMIT 1",
+                [],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()?;
+
+        let title = metadata("title")?
+            .or_else(|| root_collection.as_ref().map(|(title, _)| title.clone()))
+            .unwrap_or_else(|| db.file_stem().unwrap_or_default().to_string_lossy().into());
+        let uri = metadata("uri")?
+            .or_else(|| root_collection.map(|(_, uri)| uri))
+            .unwrap_or_default();
+
+        let mut stmt = conn.prepare("SELECT nav_item.subitem_id, subitem.title, nav_item.uri, IIF(nav_collection.nav_section_id IS NULL, nav_item.title, nav_collection.title) FROM nav_item JOIN subitem ON nav_item.subitem_id = subitem.id JOIN nav_section ON nav_item.nav_section_id = nav_section.id JOIN nav_collection ON nav_collection.id = nav_section.nav_collection_id ORDER BY nav_item.position")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(SqliteRow {
+                id: row.get(0)?,
+                chapter_title: row.get(1)?,
+                uri: row.get(2)?,
+                book_title: row.get(3)?,
+            })
+        })?;
+
+        let mut books: Vec<Book> = vec![];
+        for row in rows {
+            let row = row?;
+
+            let chapter = ChapterEntry {
+                id: row.id,
+                title: row.chapter_title,
+                uri: row.uri,
+            };
+            match books.last_mut() {
+                Some(book) if book.title == row.book_title => book.chapters.push(chapter),
+                _ => books.push(Book {
+                    title: row.book_title,
+                    chapters: vec![chapter],
+                }),
+            }
+        }
+        drop(stmt);
+
+        Ok(Work {
+            abbreviation: abbreviate(&title),
+            title,
+            uri,
+            books,
+            error: None,
+            path: db.to_path_buf(),
+            conn: Some(conn),
+        })
+    }
+
+    /// Reads the content and footnotes of one chapter from the database.
+    pub(crate) fn load_chapter(&self, entry: &ChapterEntry) -> AppResult<Chapter> {
+        let Some(conn) = &self.conn else {
+            return Err(format!("{} is not loaded", self.title).into());
+        };
+
+        let html_content = conn.query_row(
+            "SELECT content_html FROM subitem_content WHERE subitem_id = ?",
+            [&entry.id],
+            |row| row.get(0),
+        )?;
+
+        let mut stmt = conn.prepare_cached("SELECT label_html, content_html, ref_id, origin_id FROM related_content_item WHERE subitem_id = :id")?;
+        let footnote_rows = stmt.query_map(&[(":id", &entry.id)], |row| {
+            Ok(Footnote {
+                label_html: row.get(0)?,
+                content_html: row.get(1)?,
+                id: row.get(2)?,
+                origin_id: row.get(3)?,
+            })
+        })?;
+
+        let mut footnotes = HashMap::new();
+        for footnote in footnote_rows {
+            let footnote = footnote?;
+            footnotes.insert(footnote.id.clone(), footnote);
+        }
+
+        Ok(Chapter {
+            html_content,
+            footnotes,
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Book {
+    pub(crate) title: String,
+    pub(crate) chapters: Vec<ChapterEntry>,
+}
+
+/// A chapter as listed in the navigation index, before its content is loaded.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ChapterEntry {
+    /// `subitem.id` of the chapter.
+    pub(crate) id: String,
+    pub(crate) title: String,
+    /// Content URI, such as "/scriptures/pgp/moses/1".
+    pub(crate) uri: String,
+}
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Chapter {
+    html_content: String,
+    footnotes: HashMap<String, Footnote>,
+}
+
+impl Chapter {
+    /// Every footnote in the chapter, in the order their markers appear.
+    pub(crate) fn footnotes_text(&self) -> Vec<FootnoteText> {
+        let refs_in_order = self.refs_in_order();
+        let mut result = vec![];
+        for ref_id in &refs_in_order {
+            if let Some(footnote) = self.footnotes.get(ref_id) {
+                let wrapped_label = format!("<p>{}</p>", footnote.label_html);
+                let title = html::with_document(&wrapped_label, |tree, _| {
+                    let mut title = String::new();
+                    if let Some(tree) = tree {
+                        recursive_text_as_string(tree.root(), &mut title);
+                    }
+                    title
+                });
+
+                let mut spans = vec![Span::styled(
+                    title.clone(),
+                    Style::default().add_modifier(Modifier::BOLD),
+                )];
+                let mut links = vec![];
+                html::with_document(&footnote.content_html, |tree, _| {
+                    if let Some(tree) = tree {
+                        footnote_spans(tree.root(), &mut spans, &mut links);
+                    }
+                });
+
+                result.push(FootnoteText {
+                    ref_id: ref_id.clone(),
+                    origin_id: footnote.origin_id.clone(),
+                    label: title,
+                    line: Line::from(spans),
+                    links,
+                });
+            }
+        }
+
+        result
+    }
+
+    fn refs_in_order(&self) -> Vec<String> {
+        html::with_document(&self.html_content, |tree, _| {
+            tree.into_iter()
+                .flat_map(|tree| tree.descendants())
+                .filter(|n| n.attribute("class") == Some("study-note-ref"))
+                .filter_map(|n| n.attribute("data-ref"))
+                .map(String::from)
+                .collect()
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+struct Footnote {
+    id: String,
+    origin_id: String,
+    label_html: String,
+    content_html: String,
+}
+
+impl Chapter {
+    /// Renders the chapter, marking the text covered by `annotations`.
+    pub(crate) fn text(&self, annotations: &[Annotation]) -> ChapterText {
+        html::with_document(&self.html_content, |tree, warning| {
+            // Fragments without a `body` are rendered whole.
+            let body = tree.map(|t| {
+                t.descendants()
+                    .find(|n| n.has_tag_name("body"))
+                    .unwrap_or(t.root_element())
+            });
+            match body {
+                Some(body) => html::render_body(body, annotations, warning),
+                None => ChapterText::default(),
+            }
+        })
+    }
+}
+
+/// A chapter rendered for display, along with where each verse begins.
+#[derive(Debug, Default, Clone)]
+pub struct ChapterText {
+    pub text: Text<'static>,
+    pub verses: Vec<Verse>,
+}
+
+/// A verse paragraph within a [`ChapterText`].
+#[derive(Debug, Default, Clone)]
+pub struct Verse {
+    /// Verse number, when the paragraph has one.
+    pub number: Option<u32>,
+    /// `id` attribute of the paragraph, such as "p12".
+    pub id: String,
+    /// `data-aid` attribute of the paragraph, which stays the same when the
+    /// content is updated.
+    pub aid: String,
+    /// Index of the verse's line in [`ChapterText::text`]. Each verse is a
+    /// single line, which wraps as needed.
+    pub line: usize,
+    /// Words of the verse as rendered in its line, without its number or
+    /// footnote markers. Annotation offsets count characters of this text.
+    pub text: String,
+    /// Footnote markers in the order they appear.
+    pub markers: Vec<Marker>,
+}
+
+/// A superscript footnote marker within a verse.
+#[derive(Debug, Default, Clone)]
+pub struct Marker {
+    /// Id of the footnote it refers to, such as "note10a".
+    pub ref_id: String,
+    /// Index of the marker's span in the verse's line.
+    pub span: usize,
+}
+
+/// A footnote rendered for the footnote pane.
+#[derive(Debug, Default, Clone)]
+pub struct FootnoteText {
+    /// Id the footnote is referred to by, such as "note10a".
+    pub ref_id: String,
+    /// `id` of the verse paragraph the footnote belongs to, such as "p10".
+    pub origin_id: String,
+    /// Label such as "10a".
+    pub label: String,
+    pub line: Line<'static>,
+    /// Cross-references in the order they appear.
+    pub links: Vec<Link>,
+}
+
+/// A cross-reference from a footnote to another passage, such as
+/// `<a class="scripture-ref" href="gospellibrary://content/scriptures/bofm/1-ne/17?id=p47#p47">`.
+#[derive(Debug, Default, Clone)]
+pub struct Link {
+    /// Text of the link, such as "1 Ne. 17:47".
+    pub label: String,
+    /// URI of the chapter linked to, such as "/scriptures/bofm/1-ne/17".
+    pub uri: String,
+    /// `id` of the first verse linked to, such as "p47".
+    pub first_verse: Option<String>,
+    /// `id` of the last verse of the first linked range, for links such as
+    /// `?id=p1-p6`.
+    pub last_verse: Option<String>,
+    /// Index of the link's span in the footnote's line.
+    pub span: usize,
+}
+
+impl Link {
+    /// Parses the `href` of a scripture reference link.
+    fn parse(href: &str) -> Option<Self> {
+        let path = href.strip_prefix("gospellibrary://content")?;
+        let path = path.split('#').next().unwrap_or_default();
+        let (uri, query) = path.split_once('?').unwrap_or((path, ""));
+
+        // `id` lists verses and ranges, such as "p8,p17" or "p1-p6".
+        let ids = query
+            .split('&')
+            .find_map(|param| param.strip_prefix("id="))
+            .and_then(|ids| ids.split(',').next())
+            .filter(|ids| !ids.is_empty());
+        let (first_verse, last_verse) = match ids.map(|ids| ids.split_once('-')) {
+            Some(Some((first, last))) => (Some(first.to_string()), Some(last.to_string())),
+            Some(None) => (ids.map(String::from), None),
+            None => (None, None),
+        };
+
+        Some(Link {
+            uri: uri.to_string(),
+            first_verse,
+            last_verse,
+            ..Default::default()
+        })
+    }
+}
+
+/// Flattens footnote content to spans, keeping each scripture reference in a
+/// span of its own so it can be selected and followed.
+fn footnote_spans(node: roxmltree::Node, spans: &mut Vec<Span<'static>>, links: &mut Vec<Link>) {
+    if node.attribute("class") == Some("scripture-ref") {
+        let mut label = String::new();
+        recursive_text_as_string(node, &mut label);
+
+        match node.attribute("href").and_then(Link::parse) {
+            Some(link) => {
+                links.push(Link {
+                    label: label.clone(),
+                    span: spans.len(),
+                    ..link
+                });
+                spans.push(Span::styled(
+                    label,
+                    Style::default().add_modifier(Modifier::UNDERLINED),
+                ));
+            }
+            None => spans.push(Span::raw(label)),
+        }
+        return;
+    }
+
+    if node.is_text()
+        && let Some(t) = node.text()
+    {
+        spans.push(Span::raw(t.to_string()));
+    }
+
+    for child in node.children() {
+        footnote_spans(child, spans, links);
+    }
+}
+
+fn recursive_text_as_string(node: roxmltree::Node, s: &mut String) {
+    if node.is_text()
+        && let Some(t) = node.text()
+    {
+        s.push_str(t);
+    }
+
+    for n in node.children() {
+        recursive_text_as_string(n, s);
+    }
+}
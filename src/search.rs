@@ -0,0 +1,567 @@
+use std::{
+    env, fs,
+    io::{self, IsTerminal, Write},
+    path::Path,
+    sync::mpsc,
+    thread,
+    time::UNIX_EPOCH,
+};
+
+use regex::{Regex, RegexBuilder};
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+
+use crate::app::AppResult;
+use crate::cli::SearchMode;
+use crate::paths;
+use crate::scriptures::{Scriptures, Work};
+
+/// File name of the search index in the cache directory.
+const INDEX_FILE: &str = "search-index.db";
+
+/// Version of the verse text the index holds, kept in `PRAGMA user_version`.
+/// An index of another version is built again from scratch.
+const INDEX_VERSION: i64 = 2;
+
+/// Most results returned for one query.
+const MAX_RESULTS: usize = 500;
+
+/// Marks the start and end of a match in snippets returned by SQLite.
+const MATCH_START: char = '\u{1}';
+const MATCH_END: char = '\u{2}';
+
+/// A verse matching a search.
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    /// Human readable reference, such as "Moses 1:39".
+    pub reference: String,
+    pub work_uri: String,
+    /// `subitem.id` of the chapter the verse is in.
+    pub chapter_id: String,
+    pub verse: Option<u32>,
+    /// Excerpt of the verse around the match, as `(text, is_match)` pieces.
+    pub snippet: Vec<(String, bool)>,
+}
+
+/// SQLite FTS5 index over the text of every verse of every loaded work.
+///
+/// The index is stored in `$XDG_CACHE_HOME/scripture-tui`. A work is indexed
+/// again whenever its database file changes.
+#[derive(Debug)]
+pub struct SearchIndex {
+    conn: Connection,
+}
+
+impl SearchIndex {
+    /// Opens the index, bringing it up to date with `scriptures` first.
+    pub fn open(scriptures: &Scriptures) -> AppResult<Self> {
+        let cache_dir = paths::cache_dir().ok_or("No cache directory for search index")?;
+        fs::create_dir_all(&cache_dir)?;
+        let index = SearchIndex {
+            conn: Self::open_at(&cache_dir.join(INDEX_FILE))?,
+        };
+        for work in scriptures.works.iter().filter(|w| w.error.is_none()) {
+            index.update(work)?;
+        }
+        Ok(index)
+    }
+
+    /// Opens the index for the works in `data_dir` on another thread, since
+    /// indexing them the first time takes a while. The index, or why it
+    /// couldn't be opened, is sent once it's up to date.
+    pub fn open_in_background(data_dir: &Path) -> mpsc::Receiver<Result<Self, String>> {
+        let (sender, receiver) = mpsc::channel();
+        let data_dir = data_dir.to_path_buf();
+        thread::spawn(move || {
+            // Works can't be shared between threads, so they're loaded again.
+            let scriptures = Scriptures::load(&data_dir);
+            let result = Self::open(&scriptures).map_err(|e| e.to_string());
+            // Nobody is waiting if the app has quit.
+            let _ = sender.send(result);
+        });
+        receiver
+    }
+
+    fn open_at(path: &Path) -> AppResult<Connection> {
+        let conn = Connection::open(path)?;
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version != INDEX_VERSION {
+            conn.execute_batch(&format!(
+                "DROP TABLE IF EXISTS verse;
+                 DROP TABLE IF EXISTS indexed_work;
+                 PRAGMA user_version = {INDEX_VERSION};"
+            ))?;
+        }
+        conn.execute_batch(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS verse USING fts5(text, reference UNINDEXED, work_uri UNINDEXED, chapter_id UNINDEXED, verse_number UNINDEXED);
+             CREATE TABLE IF NOT EXISTS indexed_work ( uri TEXT NOT NULL PRIMARY KEY, modified INTEGER NOT NULL, size INTEGER NOT NULL );",
+        )?;
+        Ok(conn)
+    }
+
+    /// Indexes `work` again if its database changed since it was last indexed.
+    fn update(&self, work: &Work) -> AppResult<()> {
+        let metadata = fs::metadata(&work.path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        let size = metadata.len() as i64;
+
+        let indexed = self
+            .conn
+            .query_row(
+                "SELECT modified, size FROM indexed_work WHERE uri = ?",
+                [&work.uri],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)),
+            )
+            .optional()?;
+        if indexed == Some((modified, size)) {
+            return Ok(());
+        }
+
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM verse WHERE work_uri = ?", [&work.uri])?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO verse (text, reference, work_uri, chapter_id, verse_number) VALUES (?, ?, ?, ?, ?)",
+            )?;
+            for entry in work.books.iter().flat_map(|b| &b.chapters) {
+                let chapter = work.load_chapter(entry)?;
+                for verse in chapter.text(&[]).verses {
+                    let reference = verse_reference(&entry.title, verse.number);
+                    insert.execute((
+                        &verse.text,
+                        &reference,
+                        &work.uri,
+                        &entry.id,
+                        verse.number,
+                    ))?;
+                }
+            }
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO indexed_work (uri, modified, size) VALUES (?, ?, ?)",
+            (&work.uri, modified, size),
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Finds verses containing every word of `query`, best matches first.
+    /// Text in double quotes must appear as a phrase.
+    pub fn search(&self, query: &str) -> AppResult<Vec<SearchResult>> {
+        let query = fts_query(query);
+        if query.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut stmt = self.conn.prepare(
+            "SELECT reference, work_uri, chapter_id, verse_number, snippet(verse, 0, char(1), char(2), '…', 24) FROM verse WHERE verse MATCH ? ORDER BY rank LIMIT ?",
+        )?;
+        let rows = stmt.query_map((&query, MAX_RESULTS), |row| {
+            Ok(SearchResult {
+                reference: row.get(0)?,
+                work_uri: row.get(1)?,
+                chapter_id: row.get(2)?,
+                verse: row.get(3)?,
+                snippet: split_snippet(&row.get::<_, String>(4)?),
+            })
+        })?;
+
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+}
+
+/// A verse printed by `scripture-tui search --json`.
+#[derive(Debug, Serialize)]
+struct VerseMatch<'a> {
+    reference: String,
+    work_uri: &'a str,
+    chapter_id: &'a str,
+    verse: Option<u32>,
+    text: &'a str,
+    /// Start and end of each match, in characters, as annotations count them.
+    matches: Vec<(usize, usize)>,
+}
+
+/// Runs `scripture-tui search`, printing every verse of the chosen works that
+/// matches `query`. Unlike [`SearchIndex::search`] this reads each chapter, so
+/// it can match regular expressions and word boundaries exactly.
+pub fn grep(
+    data_dir: &Path,
+    query: &str,
+    mode: SearchMode,
+    work_names: &[String],
+    case_sensitive: bool,
+    json: bool,
+) -> AppResult<()> {
+    let scriptures = Scriptures::load(data_dir);
+    let regex = search_regex(query, mode, case_sensitive)?;
+
+    let mut works = vec![];
+    for work in scriptures.works.iter().filter(|w| w.error.is_none()) {
+        if work_names.is_empty() || work_names.iter().any(|name| work_matches(work, name)) {
+            works.push(work);
+        }
+    }
+    if works.is_empty() {
+        let available = scriptures
+            .works
+            .iter()
+            .filter(|w| w.error.is_none())
+            .map(|w| w.abbreviation.as_str())
+            .collect::<Vec<_>>();
+        return Err(format!(
+            "No work matches {} (available: {})",
+            work_names.join(", "),
+            available.join(", ")
+        )
+        .into());
+    }
+
+    let styled = !json && io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+    let mut out = io::stdout().lock();
+    let mut found = false;
+    for work in works {
+        for entry in work.books.iter().flat_map(|b| &b.chapters) {
+            let chapter = work.load_chapter(entry)?;
+            for verse in chapter.text(&[]).verses {
+                let matches = regex.find_iter(&verse.text).collect::<Vec<_>>();
+                if matches.is_empty() {
+                    continue;
+                }
+                found = true;
+
+                let reference = verse_reference(&entry.title, verse.number);
+                if json {
+                    let chars = |byte: usize| verse.text[..byte].chars().count();
+                    let line = serde_json::to_string(&VerseMatch {
+                        reference,
+                        work_uri: &work.uri,
+                        chapter_id: &entry.id,
+                        verse: verse.number,
+                        text: &verse.text,
+                        matches: matches
+                            .iter()
+                            .map(|m| (chars(m.start()), chars(m.end())))
+                            .collect(),
+                    })?;
+                    writeln!(out, "{line}")?;
+                } else if styled {
+                    let text = regex.replace_all(&verse.text, "\x1b[1;31m$0\x1b[0m");
+                    writeln!(out, "\x1b[1m{reference}:\x1b[0m {}", text.trim())?;
+                } else {
+                    writeln!(out, "{reference}: {}", verse.text.trim())?;
+                }
+            }
+        }
+    }
+
+    if !found {
+        return Err(format!("No verses match {query}").into());
+    }
+    Ok(())
+}
+
+/// The regular expression `scripture-tui search` matches verses with.
+fn search_regex(query: &str, mode: SearchMode, case_sensitive: bool) -> AppResult<Regex> {
+    let pattern = match mode {
+        SearchMode::Phrase => literal_pattern(query),
+        // Unlike `\b`, these also hold next to punctuation, as in "(faith)".
+        SearchMode::Word => format!(r"\b{{start-half}}{}\b{{end-half}}", literal_pattern(query)),
+        SearchMode::Regex => query.to_string(),
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!case_sensitive)
+        .build()
+        .map_err(|e| format!("Invalid search: {e}").into())
+}
+
+/// A regular expression matching `query` literally, except that any run of
+/// whitespace matches any other and a straight apostrophe also matches the
+/// curly one the scriptures use.
+fn literal_pattern(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| regex::escape(word).replace('\'', "['’]"))
+        .collect::<Vec<_>>()
+        .join(r"\s+")
+}
+
+/// Whether `name` is the title, abbreviation or last URI segment of `work`,
+/// ignoring case.
+fn work_matches(work: &Work, name: &str) -> bool {
+    let name = name.trim();
+    [
+        work.title.as_str(),
+        work.abbreviation.as_str(),
+        work.uri.rsplit('/').next().unwrap_or_default(),
+    ]
+    .iter()
+    .any(|candidate| candidate.eq_ignore_ascii_case(name))
+}
+
+/// A human readable reference to a verse of the chapter titled
+/// `chapter_title`, such as "Moses 1:39".
+fn verse_reference(chapter_title: &str, verse: Option<u32>) -> String {
+    match verse {
+        Some(number) => format!("{chapter_title}:{number}"),
+        None => chapter_title.to_string(),
+    }
+}
+
+/// Turns what was typed into an FTS5 query. Quoted text is kept together as a
+/// phrase and every other word becomes its own term, so punctuation can't be
+/// mistaken for query syntax.
+fn fts_query(input: &str) -> String {
+    let mut terms = vec![];
+    for (i, part) in input.split('"').enumerate() {
+        if i % 2 == 1 {
+            terms.push(part.trim().to_string());
+        } else {
+            terms.extend(part.split_whitespace().map(String::from));
+        }
+    }
+
+    terms
+        .iter()
+        .filter(|t| !t.is_empty())
+        .map(|t| format!("\"{t}\""))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Splits a snippet marked with [`MATCH_START`] and [`MATCH_END`] into pieces.
+fn split_snippet(snippet: &str) -> Vec<(String, bool)> {
+    let mut pieces = vec![];
+    let mut current = String::new();
+    let mut in_match = false;
+    for c in snippet.chars() {
+        if c == MATCH_START || c == MATCH_END {
+            if !current.is_empty() {
+                pieces.push((std::mem::take(&mut current), in_match));
+            }
+            in_match = c == MATCH_START;
+        } else {
+            current.push(c);
+        }
+    }
+    if !current.is_empty() {
+        pieces.push((current, in_match));
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An index holding `verses`, as `(reference, text)`.
+    fn index(verses: &[(&str, &str)]) -> SearchIndex {
+        let conn = SearchIndex::open_at(Path::new(":memory:")).unwrap();
+        for (reference, text) in verses {
+            conn.execute(
+                "INSERT INTO verse (text, reference, work_uri, chapter_id, verse_number) VALUES (?, ?, '/scriptures/pgp', '1', 1)",
+                (text, reference),
+            )
+            .unwrap();
+        }
+        SearchIndex { conn }
+    }
+
+    fn references(index: &SearchIndex, query: &str) -> Vec<String> {
+        let mut references = index
+            .search(query)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.reference)
+            .collect::<Vec<_>>();
+        references.sort();
+        references
+    }
+
+    fn verse_count(index: &SearchIndex) -> i64 {
+        index
+            .conn
+            .query_row("SELECT count(*) FROM verse", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn quotes_each_word_and_phrase() {
+        assert_eq!(fts_query("faith hope"), r#""faith" "hope""#);
+        assert_eq!(
+            fts_query(r#"  "eternal   life" glory "#),
+            r#""eternal   life" "glory""#
+        );
+        assert_eq!(fts_query(r#""eternal life"#), r#""eternal life""#);
+        assert_eq!(
+            fts_query(r#"wor* -not NEAR(a b) col:x"#),
+            r#""wor*" "-not" "NEAR(a" "b)" "col:x""#
+        );
+        assert_eq!(fts_query(r#" "" "#), "");
+    }
+
+    #[test]
+    fn query_syntax_is_searched_for_literally() {
+        let index = index(&[
+            ("Moses 1:39", "For behold, this is my work and my glory"),
+            ("Moses 1:6", "I have a work for thee, Moses, my son"),
+            (
+                "Abraham 3:22",
+                "the intelligences that were organized before the world was",
+            ),
+        ]);
+
+        assert_eq!(references(&index, "work glory"), ["Moses 1:39"]);
+        assert_eq!(references(&index, "work"), ["Moses 1:39", "Moses 1:6"]);
+        assert_eq!(references(&index, r#""my glory""#), ["Moses 1:39"]);
+        assert!(references(&index, r#""glory my""#).is_empty());
+        for query in ["wor*", "-work", "work AND", "NOT work", "glory)", "\"", ""] {
+            assert!(index.search(query).is_ok(), "{query}");
+        }
+    }
+
+    #[test]
+    fn snippets_mark_the_matches() {
+        let index = index(&[("Moses 1:39", "this is my work and my glory")]);
+        let results = index.search("glory").unwrap();
+
+        assert_eq!(
+            results[0].snippet,
+            [
+                ("this is my work and my ".into(), false),
+                ("glory".into(), true)
+            ]
+        );
+    }
+
+    #[test]
+    fn indexes_of_another_version_are_rebuilt() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(INDEX_FILE);
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE verse (text); INSERT INTO verse VALUES ('old'); PRAGMA user_version = 1;",
+        )
+        .unwrap();
+        drop(conn);
+
+        let index = SearchIndex {
+            conn: SearchIndex::open_at(&path).unwrap(),
+        };
+        let version: i64 = index
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, INDEX_VERSION);
+        assert_eq!(verse_count(&index), 0);
+
+        // Opening an index of this version keeps what's in it.
+        index
+            .conn
+            .execute("INSERT INTO verse (text) VALUES ('new')", [])
+            .unwrap();
+        drop(index);
+        let index = SearchIndex {
+            conn: SearchIndex::open_at(&path).unwrap(),
+        };
+        assert_eq!(verse_count(&index), 1);
+    }
+
+    #[test]
+    fn works_are_indexed_again_when_their_database_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut work = Work::default();
+        work.uri = "/scriptures/pgp".into();
+        work.path = dir.path().join("pgp.sqlite");
+        fs::write(&work.path, "database").unwrap();
+
+        let index = index(&[]);
+        index.update(&work).unwrap();
+        // A verse that only stays if the work isn't indexed again.
+        index
+            .conn
+            .execute(
+                "INSERT INTO verse (text, work_uri) VALUES ('kept', '/scriptures/pgp')",
+                [],
+            )
+            .unwrap();
+
+        index.update(&work).unwrap();
+        assert_eq!(verse_count(&index), 1);
+
+        fs::write(&work.path, "a longer database").unwrap();
+        index.update(&work).unwrap();
+        assert_eq!(verse_count(&index), 0);
+    }
+
+    fn matches(query: &str, mode: SearchMode, text: &str) -> Vec<String> {
+        search_regex(query, mode, false)
+            .unwrap()
+            .find_iter(text)
+            .map(|m| m.as_str().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn phrases_match_literally() {
+        let text = "Is it a.b? It is—wait: thou shalt not";
+        assert_eq!(matches("a.b?", SearchMode::Phrase, text), ["a.b?"]);
+        assert!(matches("a*b", SearchMode::Phrase, text).is_empty());
+        assert_eq!(matches("it", SearchMode::Phrase, text), ["it", "It", "it"]);
+        assert_eq!(
+            matches("shalt   not", SearchMode::Phrase, "thou shalt\nnot"),
+            ["shalt\nnot"]
+        );
+        assert_eq!(
+            matches("Lord's", SearchMode::Phrase, "the Lord’s"),
+            ["Lord’s"]
+        );
+    }
+
+    #[test]
+    fn words_match_whole_words() {
+        let text = "faithful faith, unfaith";
+        assert_eq!(matches("faith", SearchMode::Word, text), ["faith"]);
+        assert_eq!(matches("faith", SearchMode::Phrase, text).len(), 3);
+        assert_eq!(
+            matches("(faith)", SearchMode::Word, "(faith) a(faith)"),
+            ["(faith)"]
+        );
+    }
+
+    #[test]
+    fn regexes_are_used_as_given() {
+        let text = "Moses 1:39 and Moses 7";
+        assert_eq!(
+            matches(r"Moses \d+(:\d+)?", SearchMode::Regex, text),
+            ["Moses 1:39", "Moses 7"]
+        );
+        assert!(search_regex("(unclosed", SearchMode::Regex, false).is_err());
+    }
+
+    #[test]
+    fn case_is_ignored_unless_asked_not_to() {
+        let regex = search_regex("lord", SearchMode::Word, true).unwrap();
+        assert!(!regex.is_match("the Lord"));
+        assert!(regex.is_match("the lord"));
+    }
+
+    #[test]
+    fn works_match_by_title_abbreviation_or_uri() {
+        let mut work = Work::default();
+        work.title = "Pearl of Great Price".into();
+        work.abbreviation = "PoGP".into();
+        work.uri = "/scriptures/pgp".into();
+
+        for name in ["Pearl of Great Price", "pogp", " PGP ", "pgp"] {
+            assert!(work_matches(&work, name), "{name}");
+        }
+        for name in ["Pearl", "scriptures", ""] {
+            assert!(!work_matches(&work, name), "{name}");
+        }
+    }
+}
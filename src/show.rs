@@ -0,0 +1,123 @@
+use std::{
+    env,
+    io::{self, IsTerminal, Write},
+    path::Path,
+};
+
+use tui::{
+    style::{Modifier, Style},
+    text::Line,
+};
+
+use crate::app::AppResult;
+use crate::reference::Reference;
+use crate::scriptures::Scriptures;
+
+/// Runs `scripture-tui show`, printing the passage `reference` names. The
+/// text is styled with ANSI escape codes when standard output is a terminal
+/// and `$NO_COLOR` isn't set.
+pub fn show(data_dir: &Path, reference: &str, footnotes: bool) -> AppResult<()> {
+    let mut scriptures = Scriptures::load(data_dir);
+    if scriptures.works.is_empty() {
+        return Err(format!("No scripture databases found in {}", data_dir.display()).into());
+    }
+    let parsed = Reference::parse(reference).map_err(|e| format!("{reference}: {e}"))?;
+    let location = parsed.resolve(&scriptures)?;
+    let chapter = scriptures
+        .chapter(location.work, location.book, location.chapter)?
+        .ok_or_else(|| format!("No chapter matches {reference}"))?;
+    let text = chapter.text(&[]);
+
+    let verses = text
+        .verses
+        .iter()
+        .filter(|v| {
+            parsed.verses.is_empty()
+                || v.number
+                    .is_some_and(|n| parsed.verses.iter().any(|r| r.contains(&n)))
+        })
+        .collect::<Vec<_>>();
+    if verses.is_empty() && !parsed.verses.is_empty() {
+        return Err(format!("No verses match {reference}").into());
+    }
+
+    // Just the verses asked for, or the whole chapter with its headings.
+    let lines = if parsed.verses.is_empty() {
+        text.text.lines.iter().collect::<Vec<_>>()
+    } else {
+        verses
+            .iter()
+            .filter_map(|v| text.text.lines.get(v.line))
+            .collect()
+    };
+
+    let styled = io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+    let mut out = io::stdout().lock();
+    // Keep the chapter's paragraph breaks, but only one blank line at a time
+    // and none before the first line or after the last.
+    let mut blank = false;
+    let mut started = false;
+    for line in lines {
+        let string = line_to_string(line, styled);
+        if string.trim().is_empty() {
+            blank = started;
+            continue;
+        }
+        if blank {
+            writeln!(out)?;
+            blank = false;
+        }
+        writeln!(out, "{}", string.trim_end())?;
+        started = true;
+    }
+
+    if footnotes {
+        let shown = verses.iter().map(|v| v.id.as_str()).collect::<Vec<_>>();
+        let notes = chapter
+            .footnotes_text()
+            .into_iter()
+            .filter(|f| shown.contains(&f.origin_id.as_str()))
+            .collect::<Vec<_>>();
+        if !notes.is_empty() {
+            writeln!(out)?;
+        }
+        for note in notes {
+            writeln!(out, "{}", line_to_string(&note.line, styled).trim_end())?;
+        }
+    }
+    Ok(())
+}
+
+/// The text of `line`, with ANSI escape codes for its bold, italic and
+/// underlined spans if `styled` is set.
+fn line_to_string(line: &Line, styled: bool) -> String {
+    if !styled {
+        return line.spans.iter().map(|s| s.content.as_ref()).collect();
+    }
+
+    let mut string = String::new();
+    for span in &line.spans {
+        let codes = sgr_codes(line.style.patch(span.style));
+        if codes.is_empty() {
+            string.push_str(&span.content);
+        } else {
+            string.push_str(&format!("\x1b[{}m{}\x1b[0m", codes.join(";"), span.content));
+        }
+    }
+    string
+}
+
+/// SGR parameters for the modifiers of `style`.
+fn sgr_codes(style: Style) -> Vec<&'static str> {
+    [
+        (Modifier::BOLD, "1"),
+        (Modifier::DIM, "2"),
+        (Modifier::ITALIC, "3"),
+        (Modifier::UNDERLINED, "4"),
+        (Modifier::REVERSED, "7"),
+    ]
+    .into_iter()
+    .filter(|(modifier, _)| style.add_modifier.contains(*modifier))
+    .map(|(_, code)| code)
+    .collect()
+}
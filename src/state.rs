@@ -0,0 +1,48 @@
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::AppResult;
+use crate::paths;
+
+/// File name of the session state in the state directory.
+const STATE_FILE: &str = "state.toml";
+
+/// Where the reader was when the application last quit.
+///
+/// Chapters and verses are saved by their ids in the databases rather than
+/// their position in the lists, so they are still found after works are
+/// installed or removed.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionState {
+    /// URI of the work, such as "/scriptures/pgp".
+    pub work_uri: Option<String>,
+    /// `subitem.id` of the chapter.
+    pub chapter_id: Option<String>,
+    /// `id` of the verse the cursor was on, such as "p30".
+    pub verse_id: Option<String>,
+    pub text_scroll: u16,
+    pub footnote_scroll: u16,
+    pub column_selected: usize,
+    /// Were the chapters being read as one document, without the lists?
+    pub reader_mode: bool,
+}
+
+impl SessionState {
+    /// Reads the state saved by the last session. A missing or unreadable
+    /// file is the same as no saved state.
+    pub fn load() -> Option<Self> {
+        let path = paths::state_dir()?.join(STATE_FILE);
+        let contents = fs::read_to_string(path).ok()?;
+        toml::from_str(&contents).ok()
+    }
+
+    /// Writes the state for the next session to read.
+    pub fn save(&self) -> AppResult<()> {
+        let dir = paths::state_dir().ok_or("No state directory")?;
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(STATE_FILE), toml::to_string(self)?)?;
+        Ok(())
+    }
+}
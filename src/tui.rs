@@ -1,11 +1,44 @@
 use crate::app::{App, AppResult};
 use crate::event::EventHandler;
 use crate::ui;
+use crossterm::cursor::Show;
 use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
 use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
-use std::io;
-use tui::backend::Backend;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{fs, panic, process::Command};
 use tui::Terminal;
+use tui::backend::Backend;
+
+/// Whether the terminal is in raw mode on the alternate screen, and needs to
+/// be restored before exiting.
+static TERMINAL_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Installs a panic hook that restores the terminal before the panic message
+/// and its location are printed, so they aren't lost on the alternate screen
+/// and the shell isn't left in raw mode.
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = restore();
+        default_hook(info);
+    }));
+}
+
+/// Leaves raw mode, the alternate screen and mouse capture, if the terminal
+/// is still in them.
+fn restore() -> AppResult<()> {
+    if TERMINAL_ACTIVE.swap(false, Ordering::SeqCst) {
+        terminal::disable_raw_mode()?;
+        crossterm::execute!(
+            io::stderr(),
+            LeaveAlternateScreen,
+            DisableMouseCapture,
+            Show
+        )?;
+    }
+    Ok(())
+}
 
 /// Representation of a terminal user interface.
 ///
@@ -29,6 +62,7 @@ impl<B: Backend> Tui<B> {
     ///
     /// It enables the raw mode and sets terminal properties.
     pub fn init(&mut self) -> AppResult<()> {
+        TERMINAL_ACTIVE.store(true, Ordering::SeqCst);
         terminal::enable_raw_mode()?;
         crossterm::execute!(io::stderr(), EnterAlternateScreen, EnableMouseCapture)?;
         self.terminal.hide_cursor()?;
@@ -45,13 +79,56 @@ impl<B: Backend> Tui<B> {
         Ok(())
     }
 
+    /// Hands the terminal to `editor` to edit `text`, returning the edited
+    /// text. `editor` may include arguments, such as `code --wait`.
+    pub fn edit_externally(&mut self, editor: &str, text: &str) -> AppResult<String> {
+        let mut args = editor.split_whitespace();
+        let program = args.next().ok_or("No editor given")?;
+
+        // A new file with a random name only the reader can open, so nobody
+        // else can have put anything in its place. It's removed when dropped.
+        let mut file = tempfile::Builder::new()
+            .prefix("scripture-tui-")
+            .suffix(".md")
+            .tempfile()?;
+        file.write_all(text.as_bytes())?;
+        let path = file.into_temp_path();
+
+        // Whatever happens to the editor, take the terminal back and read
+        // events again before reporting it.
+        self.events.pause();
+        let status = self.exit().and_then(|()| {
+            Command::new(program)
+                .args(args)
+                .arg(&path)
+                .status()
+                .map_err(|e| format!("Unable to run {editor}: {e}").into())
+        });
+        let restored = self.init();
+        self.events.resume();
+        restored?;
+
+        let status = status?;
+        if !status.success() {
+            return Err(format!("{editor} exited with {status}").into());
+        }
+        Ok(fs::read_to_string(&path)?)
+    }
+
     /// Exits the terminal interface.
     ///
     /// It disables the raw mode and reverts back the terminal properties.
     pub fn exit(&mut self) -> AppResult<()> {
-        terminal::disable_raw_mode()?;
-        crossterm::execute!(io::stderr(), LeaveAlternateScreen, DisableMouseCapture)?;
+        restore()?;
         self.terminal.show_cursor()?;
         Ok(())
     }
 }
+
+impl<B: Backend> Drop for Tui<B> {
+    /// Restores the terminal if [`Tui::exit`] wasn't reached, such as when an
+    /// error is returned early.
+    fn drop(&mut self) {
+        let _ = restore();
+    }
+}
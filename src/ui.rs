@@ -1,11 +1,12 @@
 use tui::{
+    Frame,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    widgets::{Block, BorderType, Borders, List, ListItem, Paragraph, Wrap},
-    Frame,
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, Paragraph, Wrap},
 };
 
-use crate::app::App;
+use crate::app::{App, BookmarkRow, FOOTNOTE_COLUMN, Mode, TEXT_COLUMN};
 
 const HIGHLIGHT_SYMBOL: &str = ">";
 
@@ -19,48 +20,106 @@ fn highlight_style(selected: bool) -> Style {
     }
 }
 
+/// Border of a pane, in color while it has keyboard focus.
+fn pane_border_style(focused: bool) -> Style {
+    if focused {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default()
+    }
+}
+
 /// Renders the user interface widgets.
 pub fn render(app: &mut App, frame: &mut Frame<'_>) {
-    let chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Length(8),
-            Constraint::Length(1),
-            Constraint::Length(20),
-            Constraint::Length(1),
-            Constraint::Length(16),
-            Constraint::Length(1),
-            Constraint::Percentage(100),
-        ])
-        .split(frame.area());
+    let area = if app.status.is_some() || matches!(app.mode, Mode::Prompt(_)) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)])
+            .split(frame.area());
+        render_status_line(app, frame, rows[1]);
+        rows[0]
+    } else {
+        frame.area()
+    };
 
-    render_works_list(app, frame, chunks[0]);
-    render_books_list(app, frame, chunks[2]);
-    render_chapters_list(app, frame, chunks[4]);
+    let text_area = if app.reader_mode {
+        // Nothing but the chapters, and no hidden list to click on.
+        app.list_rects = [Rect::default(); 3];
+        area
+    } else {
+        // Wide enough for the longest work title plus the highlight symbol.
+        let works_width = app
+            .works_titles()
+            .iter()
+            .map(|t| t.chars().count() + HIGHLIGHT_SYMBOL.len() + 1)
+            .max()
+            .unwrap_or_default()
+            .max(8) as u16;
+
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Length(works_width),
+                Constraint::Length(1),
+                Constraint::Length(20),
+                Constraint::Length(1),
+                Constraint::Length(16),
+                Constraint::Length(1),
+                Constraint::Percentage(100),
+            ])
+            .split(area);
+
+        render_works_list(app, frame, chunks[0]);
+        render_books_list(app, frame, chunks[2]);
+        render_chapters_list(app, frame, chunks[4]);
+        chunks[6]
+    };
+
+    render_chapter(app, frame, text_area);
 
-    render_chapter(app, frame, chunks[6])
+    match app.mode {
+        Mode::SearchResults => render_search_results(app, frame, text_area),
+        Mode::Bookmarks => render_bookmarks(app, frame, text_area),
+        Mode::Help => render_help(app, frame, text_area),
+        _ => {}
+    }
+}
+
+/// Block around a list, recording where its rows are drawn for mouse clicks.
+fn list_block(app: &mut App, list: usize, title: &'static str, rect: Rect) -> Block<'static> {
+    let block = Block::default()
+        .title_alignment(Alignment::Center)
+        .borders(Borders::TOP)
+        .title(title);
+    app.list_rects[list] = block.inner(rect);
+    block
 }
 
 fn render_works_list(app: &mut App, frame: &mut Frame<'_>, rect: Rect) {
+    let block = list_block(app, 0, "Work", rect);
     let works = List::new(
         app.works_titles()
             .into_iter()
-            .map(ListItem::new)
+            .enumerate()
+            .map(|(i, title)| {
+                let item = ListItem::new(title);
+                if app.work_available(i) {
+                    item
+                } else {
+                    item.style(Style::default().fg(Color::DarkGray))
+                }
+            })
             .collect::<Vec<_>>(),
     )
     .highlight_style(highlight_style(app.column_selected == 0))
     .highlight_symbol(HIGHLIGHT_SYMBOL)
-    .block(
-        Block::default()
-            .title_alignment(Alignment::Center)
-            .borders(Borders::TOP)
-            .title("Work"),
-    );
+    .block(block);
 
     frame.render_stateful_widget(works, rect, &mut app.works_state);
 }
 
 fn render_books_list(app: &mut App, frame: &mut Frame<'_>, rect: Rect) {
+    let block = list_block(app, 1, "Book", rect);
     let books = List::new(
         app.books_titles()
             .into_iter()
@@ -69,17 +128,13 @@ fn render_books_list(app: &mut App, frame: &mut Frame<'_>, rect: Rect) {
     )
     .highlight_style(highlight_style(app.column_selected == 1))
     .highlight_symbol(HIGHLIGHT_SYMBOL)
-    .block(
-        Block::default()
-            .title_alignment(Alignment::Center)
-            .borders(Borders::TOP)
-            .title("Book"),
-    );
+    .block(block);
 
     frame.render_stateful_widget(books, rect, &mut app.books_state);
 }
 
 fn render_chapters_list(app: &mut App, frame: &mut Frame<'_>, rect: Rect) {
+    let block = list_block(app, 2, "CH", rect);
     let chapters = List::new(
         app.chapters_titles()
             .into_iter()
@@ -88,57 +143,282 @@ fn render_chapters_list(app: &mut App, frame: &mut Frame<'_>, rect: Rect) {
     )
     .highlight_style(highlight_style(app.column_selected == 2))
     .highlight_symbol(HIGHLIGHT_SYMBOL)
-    .block(
-        Block::default()
-            .title_alignment(Alignment::Center)
-            .borders(Borders::TOP)
-            .title("CH"),
-    );
+    .block(block);
 
     frame.render_stateful_widget(chapters, rect, &mut app.chapters_state);
 }
 
 fn render_chapter(app: &mut App, frame: &mut Frame<'_>, rect: Rect) {
     let chapter_title = app.chapter_title();
-    let chapter = Block::default()
+    let mut chapter = Block::default()
         .title(chapter_title)
         .title_alignment(Alignment::Center)
         .borders(Borders::ALL)
-        .border_type(BorderType::Rounded);
+        .border_type(BorderType::Rounded)
+        .border_style(pane_border_style(app.column_selected == TEXT_COLUMN));
+    // Keys typed towards a binding, such as the `5` of `5j`.
+    if let Some(pending) = app.keymap.pending() {
+        chapter = chapter.title_bottom(Line::from(pending).right_aligned());
+    }
 
-    let rect_inside_block = chapter.inner(rect);
+    let mut rect_inside_block = chapter.inner(rect);
     frame.render_widget(chapter, rect);
 
+    if app.note_panel {
+        let columns = Layout::default()
+            .constraints([Constraint::Percentage(65), Constraint::Percentage(35)])
+            .direction(Direction::Horizontal)
+            .split(rect_inside_block);
+        rect_inside_block = columns[0];
+        render_note_panel(app, frame, columns[1]);
+    }
+
     let chunks = Layout::default()
         .constraints([Constraint::Percentage(80), Constraint::Percentage(20)])
         .direction(Direction::Vertical)
         .split(rect_inside_block);
 
+    let footnote_block = footnote_block(
+        app.footnote_pane_title(),
+        app.column_selected == FOOTNOTE_COLUMN,
+    );
+    app.set_text_rect(chunks[0]);
+    app.footnote_rect = footnote_block.inner(chunks[1]);
+    app.clamp_scroll();
+
     render_chapter_text(app, frame, chunks[0]);
-    render_footnotes(app, frame, chunks[1]);
+    render_footnotes(app, footnote_block, frame, chunks[1]);
 }
 
 fn render_chapter_text(app: &mut App, frame: &mut Frame<'_>, rect: Rect) {
-    let text = Paragraph::new(app.chapter_text())
+    let selected = app.selected_verses();
+    let selected_footnote = app.selected_footnote;
+    let selected_marker = match (selected_footnote, app.cursor_verse()) {
+        (Some(i), Some(verse)) => verse.markers.get(i).map(|m| (verse.line, m.span)),
+        _ => None,
+    };
+    let rendered = app.rendered_chapter();
+    let mut text = rendered.text.clone();
+    for verse in selected.into_iter().flatten() {
+        if let Some(line) = rendered
+            .verses
+            .get(verse)
+            .and_then(|v| text.lines.get_mut(v.line))
+        {
+            line.style = line.style.bg(Color::DarkGray);
+        }
+    }
+    if let Some(span) =
+        selected_marker.and_then(|(line, span)| text.lines.get_mut(line)?.spans.get_mut(span))
+    {
+        span.style = span.style.add_modifier(Modifier::REVERSED);
+    }
+
+    let line_count = rendered.line_count;
+    let text = Paragraph::new(text)
         .scroll((app.text_scroll, 0))
         .wrap(Wrap { trim: false });
     frame.render_widget(text, rect);
-    app.text_rect = rect;
+
+    if !app.reader_mode {
+        return;
+    }
+    // The chapters after this one carry on below it.
+    let mut row = line_count.saturating_sub(app.text_scroll);
+    for (text, line_count) in app.following_chapters() {
+        if row >= rect.height {
+            break;
+        }
+        let area = Rect {
+            y: rect.y + row,
+            height: line_count.min(rect.height - row),
+            ..rect
+        };
+        frame.render_widget(Paragraph::new(text).wrap(Wrap { trim: false }), area);
+        row = row.saturating_add(line_count);
+    }
 }
 
-fn render_footnotes(app: &mut App, frame: &mut Frame<'_>, rect: Rect) {
-    let block = Block::default()
-        .title("Footnotes")
+fn footnote_block(title: String, focused: bool) -> Block<'static> {
+    Block::default()
+        .title(title)
         .title_alignment(Alignment::Center)
-        .borders(Borders::TOP);
-
-    let footnote_content_area = block.inner(rect);
+        .borders(Borders::TOP)
+        .border_style(pane_border_style(focused))
+}
 
-    let footnotes = Paragraph::new(app.chapter_footnotes_text())
+fn render_footnotes(app: &mut App, block: Block<'_>, frame: &mut Frame<'_>, rect: Rect) {
+    let footnotes = Paragraph::new(app.footnote_pane_text())
         .scroll((app.footnote_scroll, 0))
         .wrap(Wrap { trim: false })
         .block(block);
 
     frame.render_widget(footnotes, rect);
-    app.footnote_rect = footnote_content_area;
+}
+
+fn render_note_panel(app: &mut App, frame: &mut Frame<'_>, rect: Rect) {
+    let editing = matches!(app.mode, Mode::EditingNote(_));
+    let title = if editing {
+        "Editing (^S save)".to_string()
+    } else {
+        app.note_panel_title()
+    };
+    let block = Block::default()
+        .title(title)
+        .title_alignment(Alignment::Center)
+        .borders(Borders::LEFT | Borders::TOP);
+    let inner = block.inner(rect);
+
+    let Mode::EditingNote(editor) = &app.mode else {
+        let note = Paragraph::new(app.note_panel_text())
+            .wrap(Wrap { trim: false })
+            .block(block);
+        frame.render_widget(note, rect);
+        return;
+    };
+
+    // Lines aren't wrapped while editing, so the cursor is where it appears
+    // to be.
+    let (row, column) = editor.cursor();
+    let scroll_y = (row as u16).saturating_sub(inner.height.saturating_sub(1));
+    let scroll_x = (column as u16).saturating_sub(inner.width.saturating_sub(1));
+    let text = editor
+        .lines()
+        .iter()
+        .map(|line| Line::raw(line.clone()))
+        .collect::<Vec<_>>();
+    frame.render_widget(
+        Paragraph::new(text)
+            .scroll((scroll_y, scroll_x))
+            .block(block),
+        rect,
+    );
+    frame.set_cursor_position((
+        inner.x + column as u16 - scroll_x,
+        inner.y + row as u16 - scroll_y,
+    ));
+}
+
+fn render_status_line(app: &App, frame: &mut Frame<'_>, rect: Rect) {
+    if let Mode::Prompt(prompt) = &app.mode {
+        let line = format!("{}{}", prompt.kind.prefix(), prompt.input);
+        let cursor_x = rect.x + line.chars().count() as u16;
+        frame.render_widget(Paragraph::new(line), rect);
+        frame.set_cursor_position((cursor_x.min(rect.right().saturating_sub(1)), rect.y));
+    } else if let Some(status) = &app.status {
+        let status = Paragraph::new(status.as_str()).style(Style::default().fg(Color::Yellow));
+        frame.render_widget(status, rect);
+    }
+}
+
+fn render_search_results(app: &mut App, frame: &mut Frame<'_>, rect: Rect) {
+    let match_style = Style::default()
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::BOLD);
+    let items = app
+        .search_results
+        .iter()
+        .map(|result| {
+            let mut spans = vec![
+                Span::styled(
+                    result.reference.clone(),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw("  "),
+            ];
+            spans.extend(result.snippet.iter().map(|(text, is_match)| {
+                if *is_match {
+                    Span::styled(text.clone(), match_style)
+                } else {
+                    Span::raw(text.clone())
+                }
+            }));
+            ListItem::new(Line::from(spans))
+        })
+        .collect::<Vec<_>>();
+
+    let results = List::new(items)
+        .highlight_style(highlight_style(true))
+        .highlight_symbol(HIGHLIGHT_SYMBOL)
+        .block(
+            Block::default()
+                .title("Search results")
+                .title_alignment(Alignment::Center)
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded),
+        );
+
+    frame.render_widget(Clear, rect);
+    frame.render_stateful_widget(results, rect, &mut app.search_results_state);
+}
+
+fn render_bookmarks(app: &mut App, frame: &mut Frame<'_>, rect: Rect) {
+    let items = app
+        .bookmark_rows()
+        .into_iter()
+        .map(|row| match row {
+            BookmarkRow::List(name) => ListItem::new(Span::styled(
+                name.to_string(),
+                Style::default()
+                    .add_modifier(Modifier::BOLD)
+                    .add_modifier(Modifier::UNDERLINED),
+            )),
+            BookmarkRow::Bookmark(i) => ListItem::new(format!("  {}", app.bookmarks[i].label)),
+        })
+        .collect::<Vec<_>>();
+
+    let bookmarks = List::new(items)
+        .highlight_style(highlight_style(true))
+        .highlight_symbol(HIGHLIGHT_SYMBOL)
+        .block(
+            Block::default()
+                .title("Bookmarks")
+                .title_alignment(Alignment::Center)
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded),
+        );
+
+    frame.render_widget(Clear, rect);
+    frame.render_stateful_widget(bookmarks, rect, &mut app.bookmarks_state);
+}
+
+fn render_help(app: &mut App, frame: &mut Frame<'_>, rect: Rect) {
+    let bindings = app.keymap.bindings();
+    let keys = bindings
+        .iter()
+        .map(|(_, keys)| keys.join(", "))
+        .collect::<Vec<_>>();
+    let keys_width = keys
+        .iter()
+        .map(|k| k.chars().count())
+        .max()
+        .unwrap_or_default();
+    let lines = bindings
+        .iter()
+        .zip(keys)
+        .map(|((action, _), keys)| {
+            Line::from(vec![
+                Span::styled(
+                    format!("{keys:>keys_width$}"),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw("  "),
+                Span::raw(action.description()),
+            ])
+        })
+        .collect::<Vec<_>>();
+
+    let block = Block::default()
+        .title(format!("Keys ({})", app.keymap.preset.name()))
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded);
+    let max_scroll = (lines.len() as u16).saturating_sub(block.inner(rect).height);
+    app.help_scroll = app.help_scroll.min(max_scroll);
+
+    let help = Paragraph::new(lines)
+        .scroll((app.help_scroll, 0))
+        .block(block);
+    frame.render_widget(Clear, rect);
+    frame.render_widget(help, rect);
 }
@@ -0,0 +1,537 @@
+use std::{
+    fs,
+    ops::Range,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::{Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tui::style::{Color, Modifier, Style};
+
+use crate::app::AppResult;
+use crate::paths;
+
+/// File name of the user database in the user data directory. It isn't named
+/// `*.sqlite` so it's never mistaken for a scripture database.
+const USER_FILE: &str = "user.db";
+
+/// List new bookmarks go in when no other is named.
+pub const DEFAULT_LIST: &str = "Bookmarks";
+
+/// A bookmarked verse or chapter.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bookmark {
+    #[serde(skip)]
+    pub id: i64,
+    /// Name of the list the bookmark is in.
+    pub list: String,
+    pub work_uri: String,
+    /// `subitem.id` of the chapter.
+    pub chapter_id: String,
+    /// `data-aid` of the verse, or `None` for the whole chapter.
+    pub verse_aid: Option<String>,
+    /// Human readable reference, such as "Moses 1:39".
+    pub label: String,
+}
+
+/// Color annotations are suggested to have.
+pub const DEFAULT_COLOR: &str = "yellow";
+
+/// How an [`Annotation`] marks the text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnotationKind {
+    /// A colored background.
+    Highlight,
+    /// A colored underline.
+    Underline,
+}
+
+impl AnnotationKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AnnotationKind::Highlight => "highlight",
+            AnnotationKind::Underline => "underline",
+        }
+    }
+
+    fn parse(s: &str) -> Self {
+        match s {
+            "underline" => AnnotationKind::Underline,
+            _ => AnnotationKind::Highlight,
+        }
+    }
+}
+
+/// A highlighted or underlined verse or part of a verse.
+///
+/// Like `paragraph_metadata` in the scripture databases, an annotation names
+/// its paragraph by `data-aid` and gives start and end offsets within it.
+/// Offsets count characters of the verse's text, leaving out the verse number
+/// and footnote markers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Annotation {
+    #[serde(skip)]
+    pub id: i64,
+    pub work_uri: String,
+    /// `subitem.id` of the chapter.
+    pub chapter_id: String,
+    /// `data-aid` of the verse.
+    pub paragraph_aid: String,
+    pub start_index: usize,
+    /// Offset just past the last character marked.
+    pub end_index: usize,
+    pub kind: AnnotationKind,
+    /// Color name, such as "yellow", or hex code, such as "#ffcc00".
+    pub color: String,
+}
+
+impl Annotation {
+    /// Characters of the verse's text that are marked.
+    pub fn range(&self) -> Range<usize> {
+        self.start_index..self.end_index
+    }
+
+    /// Style to add to the marked text.
+    pub fn style(&self) -> Style {
+        let color = Color::from_str(&self.color).unwrap_or(Color::Yellow);
+        match self.kind {
+            AnnotationKind::Highlight => Style::default().bg(color).fg(Color::Black),
+            AnnotationKind::Underline => Style::default()
+                .add_modifier(Modifier::UNDERLINED)
+                .underline_color(color),
+        }
+    }
+}
+
+/// The reader's note on a verse, in Markdown.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Note {
+    pub work_uri: String,
+    /// `subitem.id` of the chapter.
+    pub chapter_id: String,
+    /// `data-aid` of the verse.
+    pub paragraph_aid: String,
+    pub text: String,
+}
+
+/// Bookmarks, annotations and notes belonging to the reader, kept in
+/// `$XDG_DATA_HOME/scripture-tui/user.db`.
+///
+/// Chapters and verses are stored by their `subitem.id` and `data-aid`, which
+/// stay the same when the scripture databases are updated.
+#[derive(Debug)]
+pub struct UserData {
+    conn: Connection,
+}
+
+impl UserData {
+    /// Opens the user database, creating it if needed.
+    pub fn open() -> AppResult<Self> {
+        let dir = paths::user_data_dir().ok_or("No directory for user data")?;
+        fs::create_dir_all(&dir)?;
+
+        Self::create(Connection::open(dir.join(USER_FILE))?)
+    }
+
+    /// A user database that's gone once dropped.
+    #[cfg(test)]
+    pub(crate) fn open_in_memory() -> AppResult<Self> {
+        Self::create(Connection::open_in_memory()?)
+    }
+
+    /// Creates the tables `conn` doesn't have yet.
+    fn create(conn: Connection) -> AppResult<Self> {
+        conn.execute_batch(
+            "PRAGMA foreign_keys = ON;
+             CREATE TABLE IF NOT EXISTS bookmark_list ( id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE );
+             CREATE TABLE IF NOT EXISTS bookmark ( id INTEGER PRIMARY KEY, list_id INTEGER NOT NULL REFERENCES bookmark_list (id) ON DELETE CASCADE, work_uri TEXT NOT NULL, subitem_id TEXT NOT NULL, paragraph_aid TEXT, label TEXT NOT NULL, created INTEGER NOT NULL );
+             CREATE TABLE IF NOT EXISTS annotation ( id INTEGER PRIMARY KEY, work_uri TEXT NOT NULL, subitem_id TEXT NOT NULL, paragraph_aid TEXT NOT NULL, start_index INTEGER NOT NULL, end_index INTEGER NOT NULL, kind TEXT NOT NULL, color TEXT NOT NULL, created INTEGER NOT NULL );
+             CREATE INDEX IF NOT EXISTS annotation_subitem_id_idx ON annotation (work_uri, subitem_id);
+             CREATE TABLE IF NOT EXISTS note ( work_uri TEXT NOT NULL, subitem_id TEXT NOT NULL, paragraph_aid TEXT NOT NULL, text TEXT NOT NULL, modified INTEGER NOT NULL, PRIMARY KEY (work_uri, subitem_id, paragraph_aid) );",
+        )?;
+        Ok(UserData { conn })
+    }
+
+    /// Runs `f`, keeping either every change it makes or, if it fails, none
+    /// of them. Calls can be nested, the outermost one deciding.
+    pub fn in_transaction<T>(&self, f: impl FnOnce(&Self) -> AppResult<T>) -> AppResult<T> {
+        // Unlike `BEGIN`, savepoints can be opened within one another.
+        self.conn.execute_batch("SAVEPOINT user_data")?;
+        match f(self) {
+            Ok(value) => {
+                self.conn.execute_batch("RELEASE user_data")?;
+                Ok(value)
+            }
+            Err(e) => {
+                self.conn
+                    .execute_batch("ROLLBACK TO user_data; RELEASE user_data")?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Adds a bookmark to its list, creating the list if needed. The
+    /// bookmark's `id` is ignored.
+    pub fn add_bookmark(&self, bookmark: &Bookmark) -> AppResult<()> {
+        self.in_transaction(|data| {
+            data.conn.execute(
+                "INSERT OR IGNORE INTO bookmark_list (name) VALUES (?)",
+                [&bookmark.list],
+            )?;
+            let list_id: i64 = data.conn.query_row(
+                "SELECT id FROM bookmark_list WHERE name = ?",
+                [&bookmark.list],
+                |row| row.get(0),
+            )?;
+            data.conn.execute(
+                "INSERT INTO bookmark (list_id, work_uri, subitem_id, paragraph_aid, label, created) VALUES (?, ?, ?, ?, ?, ?)",
+                (
+                    list_id,
+                    &bookmark.work_uri,
+                    &bookmark.chapter_id,
+                    &bookmark.verse_aid,
+                    &bookmark.label,
+                    now(),
+                ),
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Every bookmark, grouped by list and oldest first within each list.
+    pub fn bookmarks(&self) -> AppResult<Vec<Bookmark>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT bookmark.id, bookmark_list.name, work_uri, subitem_id, paragraph_aid, label FROM bookmark JOIN bookmark_list ON bookmark.list_id = bookmark_list.id ORDER BY bookmark_list.name, bookmark.created, bookmark.id",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(Bookmark {
+                id: row.get(0)?,
+                list: row.get(1)?,
+                work_uri: row.get(2)?,
+                chapter_id: row.get(3)?,
+                verse_aid: row.get(4)?,
+                label: row.get(5)?,
+            })
+        })?;
+
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// Removes a bookmark, and its list if that was the last one in it.
+    pub fn delete_bookmark(&self, id: i64) -> AppResult<()> {
+        self.in_transaction(|data| {
+            data.conn
+                .execute("DELETE FROM bookmark WHERE id = ?", [id])?;
+            data.conn.execute(
+                "DELETE FROM bookmark_list WHERE id NOT IN (SELECT list_id FROM bookmark)",
+                [],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// The list a bookmark was last added to.
+    pub fn last_list(&self) -> AppResult<Option<String>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT bookmark_list.name FROM bookmark JOIN bookmark_list ON bookmark.list_id = bookmark_list.id ORDER BY bookmark.created DESC, bookmark.id DESC LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Adds an annotation. Its `id` is ignored.
+    pub fn add_annotation(&self, annotation: &Annotation) -> AppResult<()> {
+        self.conn.execute(
+            "INSERT INTO annotation (work_uri, subitem_id, paragraph_aid, start_index, end_index, kind, color, created) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            (
+                &annotation.work_uri,
+                &annotation.chapter_id,
+                &annotation.paragraph_aid,
+                annotation.start_index as i64,
+                annotation.end_index as i64,
+                annotation.kind.as_str(),
+                &annotation.color,
+                now(),
+            ),
+        )?;
+        Ok(())
+    }
+
+    /// Annotations in a chapter, oldest first so newer ones are drawn on top.
+    pub fn annotations(&self, work_uri: &str, chapter_id: &str) -> AppResult<Vec<Annotation>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT id, work_uri, subitem_id, paragraph_aid, start_index, end_index, kind, color FROM annotation WHERE work_uri = ? AND subitem_id = ? ORDER BY created, id",
+        )?;
+        let rows = stmt.query_map([work_uri, chapter_id], annotation_from_row)?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// Every annotation, oldest first.
+    pub fn all_annotations(&self) -> AppResult<Vec<Annotation>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, work_uri, subitem_id, paragraph_aid, start_index, end_index, kind, color FROM annotation ORDER BY created, id",
+        )?;
+        let rows = stmt.query_map([], annotation_from_row)?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// Notes on verses in a chapter.
+    pub fn notes(&self, work_uri: &str, chapter_id: &str) -> AppResult<Vec<Note>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT work_uri, subitem_id, paragraph_aid, text FROM note WHERE work_uri = ? AND subitem_id = ?",
+        )?;
+        let rows = stmt.query_map([work_uri, chapter_id], note_from_row)?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// Every note.
+    pub fn all_notes(&self) -> AppResult<Vec<Note>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT work_uri, subitem_id, paragraph_aid, text FROM note")?;
+        let rows = stmt.query_map([], note_from_row)?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// Saves a note, replacing any the verse already had. A note with no text
+    /// is deleted.
+    pub fn save_note(&self, note: &Note) -> AppResult<()> {
+        if note.text.trim().is_empty() {
+            self.conn.execute(
+                "DELETE FROM note WHERE work_uri = ? AND subitem_id = ? AND paragraph_aid = ?",
+                (&note.work_uri, &note.chapter_id, &note.paragraph_aid),
+            )?;
+        } else {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO note (work_uri, subitem_id, paragraph_aid, text, modified) VALUES (?, ?, ?, ?, ?)",
+                (
+                    &note.work_uri,
+                    &note.chapter_id,
+                    &note.paragraph_aid,
+                    &note.text,
+                    now(),
+                ),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Removes an annotation.
+    pub fn delete_annotation(&self, id: i64) -> AppResult<()> {
+        self.conn
+            .execute("DELETE FROM annotation WHERE id = ?", [id])?;
+        Ok(())
+    }
+}
+
+fn annotation_from_row(row: &Row) -> rusqlite::Result<Annotation> {
+    Ok(Annotation {
+        id: row.get(0)?,
+        work_uri: row.get(1)?,
+        chapter_id: row.get(2)?,
+        paragraph_aid: row.get(3)?,
+        start_index: row.get::<_, i64>(4)? as usize,
+        end_index: row.get::<_, i64>(5)? as usize,
+        kind: AnnotationKind::parse(&row.get::<_, String>(6)?),
+        color: row.get(7)?,
+    })
+}
+
+fn note_from_row(row: &Row) -> rusqlite::Result<Note> {
+    Ok(Note {
+        work_uri: row.get(0)?,
+        chapter_id: row.get(1)?,
+        paragraph_aid: row.get(2)?,
+        text: row.get(3)?,
+    })
+}
+
+/// Seconds since the Unix epoch.
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark(list: &str, chapter_id: &str, verse_aid: Option<&str>) -> Bookmark {
+        Bookmark {
+            id: 0,
+            list: list.into(),
+            work_uri: "/scriptures/pgp".into(),
+            chapter_id: chapter_id.into(),
+            verse_aid: verse_aid.map(String::from),
+            label: format!("Moses {chapter_id}"),
+        }
+    }
+
+    fn annotation(chapter_id: &str, kind: AnnotationKind, range: Range<usize>) -> Annotation {
+        Annotation {
+            id: 0,
+            work_uri: "/scriptures/pgp".into(),
+            chapter_id: chapter_id.into(),
+            paragraph_aid: "39".into(),
+            start_index: range.start,
+            end_index: range.end,
+            kind,
+            color: DEFAULT_COLOR.into(),
+        }
+    }
+
+    /// The bookmarks' lists and chapters, in the order they're listed.
+    fn listed(user_data: &UserData) -> Vec<(String, String)> {
+        user_data
+            .bookmarks()
+            .unwrap()
+            .into_iter()
+            .map(|b| (b.list, b.chapter_id))
+            .collect()
+    }
+
+    #[test]
+    fn bookmarks_are_grouped_by_list() {
+        let user_data = UserData::open_in_memory().unwrap();
+        user_data
+            .add_bookmark(&bookmark("Study", "1", Some("39")))
+            .unwrap();
+        user_data
+            .add_bookmark(&bookmark("Lesson", "2", None))
+            .unwrap();
+        user_data
+            .add_bookmark(&bookmark("Study", "3", None))
+            .unwrap();
+
+        assert_eq!(
+            listed(&user_data),
+            [
+                ("Lesson".into(), "2".into()),
+                ("Study".into(), "1".into()),
+                ("Study".into(), "3".into())
+            ]
+        );
+        assert_eq!(user_data.last_list().unwrap().as_deref(), Some("Study"));
+
+        let bookmarks = user_data.bookmarks().unwrap();
+        assert_eq!(bookmarks[1].verse_aid.as_deref(), Some("39"));
+        assert_eq!(bookmarks[1].label, "Moses 1");
+    }
+
+    #[test]
+    fn deleting_a_lists_last_bookmark_deletes_the_list() {
+        let user_data = UserData::open_in_memory().unwrap();
+        user_data
+            .add_bookmark(&bookmark("Study", "1", None))
+            .unwrap();
+        user_data
+            .add_bookmark(&bookmark("Lesson", "2", None))
+            .unwrap();
+        let lesson = user_data.bookmarks().unwrap()[0].id;
+
+        user_data.delete_bookmark(lesson).unwrap();
+
+        assert_eq!(listed(&user_data), [("Study".into(), "1".into())]);
+        let lists: i64 = user_data
+            .conn
+            .query_row("SELECT count(*) FROM bookmark_list", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(lists, 1);
+        assert_eq!(user_data.last_list().unwrap().as_deref(), Some("Study"));
+    }
+
+    #[test]
+    fn annotations_are_kept_by_chapter() {
+        let user_data = UserData::open_in_memory().unwrap();
+        let highlight = annotation("1", AnnotationKind::Highlight, 0..34);
+        let underline = annotation("1", AnnotationKind::Underline, 5..10);
+        let elsewhere = annotation("2", AnnotationKind::Highlight, 0..4);
+        for a in [&highlight, &underline, &elsewhere] {
+            user_data.add_annotation(a).unwrap();
+        }
+
+        let in_chapter = user_data.annotations("/scriptures/pgp", "1").unwrap();
+        let without_ids = in_chapter
+            .iter()
+            .map(|a| Annotation { id: 0, ..a.clone() })
+            .collect::<Vec<_>>();
+        assert_eq!(without_ids, [highlight, underline.clone()]);
+        assert_eq!(user_data.all_annotations().unwrap().len(), 3);
+
+        user_data.delete_annotation(in_chapter[0].id).unwrap();
+        let left = user_data.annotations("/scriptures/pgp", "1").unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].kind, AnnotationKind::Underline);
+        assert_eq!(left[0].range(), underline.range());
+    }
+
+    fn note(paragraph_aid: &str, text: &str) -> Note {
+        Note {
+            work_uri: "/scriptures/pgp".into(),
+            chapter_id: "1".into(),
+            paragraph_aid: paragraph_aid.into(),
+            text: text.into(),
+        }
+    }
+
+    #[test]
+    fn saving_a_note_replaces_the_verses_note() {
+        let user_data = UserData::open_in_memory().unwrap();
+        user_data.save_note(&note("39", "First")).unwrap();
+        user_data.save_note(&note("40", "Other")).unwrap();
+        user_data.save_note(&note("39", "# Purpose")).unwrap();
+
+        let mut notes = user_data.notes("/scriptures/pgp", "1").unwrap();
+        notes.sort_by(|a, b| a.paragraph_aid.cmp(&b.paragraph_aid));
+        assert_eq!(notes, [note("39", "# Purpose"), note("40", "Other")]);
+        assert!(user_data.notes("/scriptures/pgp", "2").unwrap().is_empty());
+    }
+
+    #[test]
+    fn saving_an_empty_note_deletes_it() {
+        let user_data = UserData::open_in_memory().unwrap();
+        user_data.save_note(&note("39", "First")).unwrap();
+        user_data.save_note(&note("39", " \n ")).unwrap();
+
+        assert!(user_data.all_notes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn failed_transactions_change_nothing() {
+        let user_data = UserData::open_in_memory().unwrap();
+        let result = user_data.in_transaction(|data| {
+            data.add_bookmark(&bookmark("Study", "1", None))?;
+            data.save_note(&note("39", "First"))?;
+            Err::<(), _>("Failed".into())
+        });
+
+        assert!(result.is_err());
+        assert!(user_data.bookmarks().unwrap().is_empty());
+        assert!(user_data.all_notes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn nested_transactions_roll_back_on_their_own() {
+        let user_data = UserData::open_in_memory().unwrap();
+        user_data
+            .in_transaction(|data| {
+                data.save_note(&note("39", "Kept"))?;
+                let inner = data.in_transaction(|data| {
+                    data.save_note(&note("40", "Rolled back"))?;
+                    Err::<(), _>("Failed".into())
+                });
+                assert!(inner.is_err());
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(user_data.all_notes().unwrap(), [note("39", "Kept")]);
+    }
+}